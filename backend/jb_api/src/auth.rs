@@ -1,3 +1,6 @@
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
 use axum::{
     BoxError,
     extract::FromRequestParts,
@@ -5,7 +8,10 @@ use axum::{
 };
 use lambda_http::RequestExt;
 
-use crate::response::{ApiResult, BoxApiError, MapBoxError};
+use crate::response::{BoxApiError, MapBoxError};
+
+/// Group whose members may manage levels and prompts
+pub const LEVEL_MANAGER_GROUP: &str = "LevelManager";
 
 struct Username(String);
 
@@ -66,10 +72,16 @@ where
             .await
             .map_err(|err| err.into_response())?;
 
-        Ok(AuthorizedModerator { username: sub, _extra: extra })
+        Ok(AuthorizedModerator {
+            username: sub,
+            _extra: extra,
+        })
     }
 }
 
+/// Any signed in user, identified by the username the authorizer verified
+pub type AuthorizedPlayer = AuthorizedModerator;
+
 pub type AuthorizedLevelManager = AuthorizedModerator<AssertLevelManager>;
 
 pub struct AssertLevelManager;
@@ -81,9 +93,11 @@ impl FromRequestParts<(&Username, &crate::State)> for AssertLevelManager {
         _parts: &mut axum::http::request::Parts,
         (sub, state): &(&Username, &crate::State),
     ) -> Result<Self, Self::Rejection> {
-        if is_in_group(&state.cognito, sub, "LevelManager")
+        if state
+            .groups
+            .is_in_group(&sub.0, LEVEL_MANAGER_GROUP)
             .await
-            .map_err(BoxApiError::from)?
+            .map_err(AuthorizationError::RetrieveGroups)?
         {
             Ok(AssertLevelManager)
         } else {
@@ -92,26 +106,59 @@ impl FromRequestParts<(&Username, &crate::State)> for AssertLevelManager {
     }
 }
 
-async fn is_in_group(
-    cognito: &aws_sdk_cognitoidentityprovider::Client,
-    sub: &Username,
-    group_name: &str,
-) -> ApiResult<bool> {
-    let user_pool = std::env::var("COGNITO_USER_POOL")
-        .box_error()
-        .map_err(AuthorizationError::RetrieveGroups)?;
-
-    let groups = cognito
-        .admin_list_groups_for_user()
-        .user_pool_id(user_pool)
-        .username(&sub.0)
-        .send()
-        .await
-        .box_error()
-        .map_err(AuthorizationError::RetrieveGroups)?;
-
-    Ok(groups
-        .groups()
-        .iter()
-        .any(|group| group.group_name() == Some(group_name)))
+/// Directory of the groups users belong to
+#[async_trait]
+pub trait UserGroups: Send + Sync {
+    async fn is_in_group(&self, username: &str, group_name: &str) -> Result<bool, BoxError>;
+}
+
+/// Groups of the Cognito user pool given through `COGNITO_USER_POOL`
+#[async_trait]
+impl UserGroups for aws_sdk_cognitoidentityprovider::Client {
+    async fn is_in_group(&self, username: &str, group_name: &str) -> Result<bool, BoxError> {
+        let user_pool = std::env::var("COGNITO_USER_POOL").box_error()?;
+
+        let groups = self
+            .admin_list_groups_for_user()
+            .user_pool_id(user_pool)
+            .username(username)
+            .send()
+            .await
+            .box_error()?;
+
+        Ok(groups
+            .groups()
+            .iter()
+            .any(|group| group.group_name() == Some(group_name)))
+    }
+}
+
+/// Fixed group memberships, intended for tests and local development
+#[derive(Default, Clone, Debug)]
+pub struct StaticUserGroups {
+    members: HashMap<String, HashSet<String>>,
+}
+
+impl StaticUserGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn member(mut self, username: impl Into<String>, group_name: impl Into<String>) -> Self {
+        self.members
+            .entry(username.into())
+            .or_default()
+            .insert(group_name.into());
+        self
+    }
+}
+
+#[async_trait]
+impl UserGroups for StaticUserGroups {
+    async fn is_in_group(&self, username: &str, group_name: &str) -> Result<bool, BoxError> {
+        Ok(self
+            .members
+            .get(username)
+            .is_some_and(|groups| groups.contains(group_name)))
+    }
 }
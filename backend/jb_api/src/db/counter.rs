@@ -1,10 +1,10 @@
-use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
 use axum::BoxError;
 use serde::{Deserialize, Serialize};
-use serde_dynamo::from_item;
 
 use crate::response::{ApiResult, MapBoxError};
 
+use super::Repository;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Counter {
     pub name: String,
@@ -17,6 +17,7 @@ impl Counter {
 
     pub const LEVEL_ID: &'static str = "level_id";
     pub const PROMPT_COMPONENT_ID: &'static str = "prompt_component_id";
+    pub const TEMPLATE_ID: &'static str = "template_id";
 }
 
 error_response!(CounterError {
@@ -28,26 +29,13 @@ error_response!(CounterError {
 });
 
 impl Counter {
-    pub async fn increment(
-        client: &aws_sdk_dynamodb::Client,
-        counter: &'static str,
-    ) -> ApiResult<u64> {
-        let new_value: Counter = client
-            .update_item()
-            .table_name(Self::TABLE)
-            .key(Self::PARTITION, AttributeValue::S(counter.into()))
-            .update_expression("ADD #field :amount")
-            .expression_attribute_names("#field", "count")
-            .expression_attribute_values(":amount", AttributeValue::N("1".into()))
-            .return_values(ReturnValue::AllNew)
-            .send()
+    pub async fn increment(repository: &dyn Repository, counter: &'static str) -> ApiResult<u64> {
+        let new_value = repository
+            .increment_counter(counter)
             .await
             .box_error()
-            .and_then(|output| {
-                from_item(output.attributes.expect("No attributes returned")).box_error()
-            })
             .map_err(|err| CounterError::CounterIncrementFailed { counter, err })?;
 
-        Ok(new_value.count)
+        Ok(new_value)
     }
 }
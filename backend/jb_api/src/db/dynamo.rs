@@ -0,0 +1,1081 @@
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{
+    operation::{
+        put_item::PutItemError, query::builders::QueryFluentBuilder,
+        transact_write_items::TransactWriteItemsError, update_item::UpdateItemError,
+    },
+    types::{AttributeValue, Put, ReturnValue, TransactWriteItem, Update},
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_dynamo::{from_item, from_items, to_attribute_value, to_item};
+
+use super::*;
+use crate::response::MapBoxError;
+
+/// Edits racing for the same revision number are retried this many times in total
+const REVISION_ATTEMPTS: usize = 3;
+
+pub struct DynamoRepository {
+    client: aws_sdk_dynamodb::Client,
+}
+
+impl DynamoRepository {
+    pub fn new(client: aws_sdk_dynamodb::Client) -> Self {
+        Self { client }
+    }
+
+    /// Run a `SET` update on an existing item, failing with [`RepositoryError::NotFound`]
+    /// if no item with the given partition key exists
+    async fn update_existing(
+        &self,
+        table: &str,
+        partition: &str,
+        key: AttributeValue,
+        actions: Vec<(&str, AttributeValue)>,
+    ) -> RepositoryResult<()> {
+        if actions.is_empty() {
+            return Ok(());
+        }
+
+        let mut update = self
+            .client
+            .update_item()
+            .table_name(table)
+            .key(partition, key.clone())
+            .condition_expression("#pk = :pk")
+            .expression_attribute_names("#pk", partition)
+            .expression_attribute_values(":pk", key)
+            .update_expression(set_expression(&actions));
+
+        for (field, value) in actions {
+            update = update
+                .expression_attribute_names(["#", field].concat(), field)
+                .expression_attribute_values([":", field].concat(), value);
+        }
+
+        update
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => RepositoryError::NotFound,
+                err => RepositoryError::Backend(Box::new(err)),
+            })?;
+
+        Ok(())
+    }
+
+    /// Update several levels, and optionally another item, in a single transaction,
+    /// failing with [`RepositoryError::NotFound`] if any of the items does not exist
+    async fn update_levels(
+        &self,
+        updates: Vec<(LevelID, LevelUpdate)>,
+        other: Option<Update>,
+    ) -> RepositoryResult<()> {
+        let mut items: Vec<_> = other
+            .into_iter()
+            .map(|update| TransactWriteItem::builder().update(update).build())
+            .collect();
+        for (level_id, update) in updates {
+            let actions = level_update_actions(update)?;
+            if actions.is_empty() {
+                continue;
+            }
+
+            let update = existing_item_update(
+                Level::TABLE,
+                Level::PARTITION,
+                AttributeValue::N(level_id.0.to_string()),
+                actions,
+            )?;
+            items.push(TransactWriteItem::builder().update(update).build());
+        }
+        if items.is_empty() {
+            return Ok(());
+        }
+        check_transaction_size(items.len())?;
+
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await
+            .map_err(|err| err.into_service_error());
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(TransactWriteItemsError::TransactionCanceledException(err))
+                if err
+                    .cancellation_reasons()
+                    .iter()
+                    .any(|reason| reason.code() == Some("ConditionalCheckFailed")) =>
+            {
+                Err(RepositoryError::NotFound)
+            }
+            Err(err) => Err(RepositoryError::Backend(Box::new(err))),
+        }
+    }
+
+    /// Replace the component's text and append the next revision to its history
+    ///
+    /// Returns `None` without changing anything if a concurrent edit took the revision number.
+    async fn append_component_revision(
+        &self,
+        revision: &NewComponentRevision,
+    ) -> RepositoryResult<Option<ComponentRevision>> {
+        let key = AttributeValue::N(revision.component_id.0.to_string());
+
+        let latest: Option<ComponentRevision> = self
+            .client
+            .query()
+            .table_name(ComponentRevision::TABLE)
+            .key_condition_expression("#pk = :pk")
+            .expression_attribute_names("#pk", ComponentRevision::PARTITION)
+            .expression_attribute_values(":pk", key.clone())
+            .scan_index_forward(false)
+            .limit(1)
+            .send()
+            .await
+            .box_error()
+            .and_then(|output| from_items(output.items.unwrap_or_default()).box_error())
+            .map_err(RepositoryError::Backend)?
+            .pop();
+
+        let mut revisions = Vec::new();
+        let number = match latest {
+            Some(latest) => latest.revision + 1,
+            None => {
+                let current = self.get_component(revision.component_id).await?;
+                revisions.push(ComponentRevision {
+                    component_id: revision.component_id,
+                    revision: 0,
+                    author: None,
+                    timestamp: revision.timestamp,
+                    text: current.text,
+                    restored_from: None,
+                });
+                1
+            }
+        };
+
+        let revision = ComponentRevision {
+            component_id: revision.component_id,
+            revision: number,
+            author: Some(revision.author.clone()),
+            timestamp: revision.timestamp,
+            text: revision.text.clone(),
+            restored_from: revision.restored_from,
+        };
+        revisions.push(revision.clone());
+
+        let update = Update::builder()
+            .table_name(PromptComponent::TABLE)
+            .key(PromptComponent::PARTITION, key)
+            .update_expression("SET #text = :text")
+            .condition_expression("attribute_exists(#pk)")
+            .expression_attribute_names("#pk", PromptComponent::PARTITION)
+            .expression_attribute_names("#text", PromptComponent::TEXT)
+            .expression_attribute_values(":text", AttributeValue::S(revision.text.clone()))
+            .build()
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        let mut items = vec![TransactWriteItem::builder().update(update).build()];
+        for revision in revisions {
+            // Concurrent edits race for the same revision number, only one of them succeeds
+            let put = Put::builder()
+                .table_name(ComponentRevision::TABLE)
+                .set_item(Some(
+                    to_item(revision)
+                        .box_error()
+                        .map_err(RepositoryError::Backend)?,
+                ))
+                .condition_expression("attribute_not_exists(#pk)")
+                .expression_attribute_names("#pk", ComponentRevision::PARTITION)
+                .build()
+                .box_error()
+                .map_err(RepositoryError::Backend)?;
+            items.push(TransactWriteItem::builder().put(put).build());
+        }
+
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await
+            .map_err(|err| err.into_service_error());
+
+        match result {
+            Ok(_) => Ok(Some(revision)),
+            Err(TransactWriteItemsError::TransactionCanceledException(err)) => {
+                // The component update comes first, the revision puts after it
+                let reasons = err.cancellation_reasons();
+                if reasons.first().and_then(|reason| reason.code())
+                    == Some("ConditionalCheckFailed")
+                {
+                    Err(RepositoryError::NotFound)
+                } else if reasons.iter().any(|reason| {
+                    matches!(
+                        reason.code(),
+                        Some("ConditionalCheckFailed" | "TransactionConflict")
+                    )
+                }) {
+                    Ok(None)
+                } else {
+                    Err(RepositoryError::Backend(Box::new(
+                        TransactWriteItemsError::TransactionCanceledException(err),
+                    )))
+                }
+            }
+            Err(err) => Err(RepositoryError::Backend(Box::new(err))),
+        }
+    }
+
+    /// Read all items of a table, following pagination
+    async fn scan_all<T: DeserializeOwned>(&self, table: &str) -> RepositoryResult<Vec<T>> {
+        self.client
+            .scan()
+            .table_name(table)
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await
+            .box_error()
+            .and_then(|items| from_items(items).box_error())
+            .map_err(RepositoryError::Backend)
+    }
+}
+
+/// Read all items matching a query, following pagination
+async fn query_all<T: DeserializeOwned>(query: QueryFluentBuilder) -> RepositoryResult<Vec<T>> {
+    query
+        .into_paginator()
+        .items()
+        .send()
+        .try_collect()
+        .await
+        .box_error()
+        .and_then(|items| from_items(items).box_error())
+        .map_err(RepositoryError::Backend)
+}
+
+/// Transaction item running a `SET` update on an existing item,
+/// the transaction fails if no item with the given partition key exists
+fn existing_item_update(
+    table: &str,
+    partition: &str,
+    key: AttributeValue,
+    actions: Vec<(&str, AttributeValue)>,
+) -> RepositoryResult<Update> {
+    let mut update = Update::builder()
+        .table_name(table)
+        .key(partition, key.clone())
+        .condition_expression("#pk = :pk")
+        .expression_attribute_names("#pk", partition)
+        .expression_attribute_values(":pk", key)
+        .update_expression(set_expression(&actions));
+    for (field, value) in actions {
+        update = update
+            .expression_attribute_names(["#", field].concat(), field)
+            .expression_attribute_values([":", field].concat(), value);
+    }
+
+    update.build().box_error().map_err(RepositoryError::Backend)
+}
+
+fn set_expression(actions: &[(&str, AttributeValue)]) -> String {
+    format!(
+        "SET {}",
+        actions
+            .iter()
+            .map(|(field, _)| format!("#{field} = :{field}"))
+            .join(" ,")
+    )
+}
+
+fn set_action<'a, T: Serialize>(
+    actions: &mut Vec<(&'a str, AttributeValue)>,
+    field: &'a str,
+    value: Option<T>,
+) -> RepositoryResult<()> {
+    if let Some(value) = value {
+        actions.push((
+            field,
+            to_attribute_value(value)
+                .box_error()
+                .map_err(RepositoryError::Backend)?,
+        ));
+    }
+    Ok(())
+}
+
+/// `SET` actions for the fields a [`LevelUpdate`] changes
+fn level_update_actions(
+    update: LevelUpdate,
+) -> RepositoryResult<Vec<(&'static str, AttributeValue)>> {
+    let LevelUpdate {
+        name,
+        password,
+        difficulty,
+        template_id,
+        prompt_components,
+        prompt_layout,
+        is_root,
+        next,
+        is_open,
+        model_settings,
+        solve_on_leak,
+        input_guards,
+        output_guards,
+        variables,
+        deleted_at,
+    } = update;
+
+    let mut actions = Vec::new();
+    set_action(&mut actions, Level::NAME, name)?;
+    set_action(&mut actions, Level::PASSWORD, password)?;
+    set_action(&mut actions, Level::DIFFICULTY, difficulty)?;
+    set_action(&mut actions, Level::TEMPLATE_ID, template_id)?;
+    set_action(&mut actions, Level::PROMPT_COMPONENTS, prompt_components)?;
+    set_action(&mut actions, Level::PROMPT_LAYOUT, prompt_layout)?;
+    set_action(&mut actions, Level::IS_ROOT, is_root)?;
+    set_action(&mut actions, Level::NEXT, next)?;
+    set_action(&mut actions, Level::IS_OPEN, is_open)?;
+    set_action(&mut actions, Level::MODEL_SETTINGS, model_settings)?;
+    set_action(&mut actions, Level::SOLVE_ON_LEAK, solve_on_leak)?;
+    set_action(&mut actions, Level::INPUT_GUARDS, input_guards)?;
+    set_action(&mut actions, Level::OUTPUT_GUARDS, output_guards)?;
+    set_action(&mut actions, Level::VARIABLES, variables)?;
+    set_action(&mut actions, Level::DELETED_AT, deleted_at)?;
+
+    Ok(actions)
+}
+
+#[async_trait]
+impl Repository for DynamoRepository {
+    async fn increment_counter(&self, counter: &'static str) -> RepositoryResult<u64> {
+        let new_value: Counter = self
+            .client
+            .update_item()
+            .table_name(Counter::TABLE)
+            .key(Counter::PARTITION, AttributeValue::S(counter.into()))
+            .update_expression("ADD #field :amount")
+            .expression_attribute_names("#field", "count")
+            .expression_attribute_values(":amount", AttributeValue::N("1".into()))
+            .return_values(ReturnValue::AllNew)
+            .send()
+            .await
+            .box_error()
+            .and_then(|output| {
+                from_item(output.attributes.expect("No attributes returned")).box_error()
+            })
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(new_value.count)
+    }
+
+    async fn get_levels(&self) -> RepositoryResult<Vec<Level>> {
+        self.scan_all(Level::TABLE).await
+    }
+
+    async fn get_level(&self, level_id: LevelID) -> RepositoryResult<Level> {
+        let item = self
+            .client
+            .get_item()
+            .table_name(Level::TABLE)
+            .key(Level::PARTITION, AttributeValue::N(level_id.0.to_string()))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .item
+            .ok_or(RepositoryError::NotFound)?;
+
+        from_item(item)
+            .box_error()
+            .map_err(RepositoryError::Backend)
+    }
+
+    async fn put_level(&self, level: &Level) -> RepositoryResult<()> {
+        self.client
+            .put_item()
+            .table_name(Level::TABLE)
+            .set_item(Some(
+                to_item(level)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn update_level(&self, level_id: LevelID, update: LevelUpdate) -> RepositoryResult<()> {
+        self.update_existing(
+            Level::TABLE,
+            Level::PARTITION,
+            AttributeValue::N(level_id.0.to_string()),
+            level_update_actions(update)?,
+        )
+        .await
+    }
+
+    async fn trash_level(
+        &self,
+        level_id: LevelID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()> {
+        let update = LevelUpdate {
+            deleted_at: Some(Some(deleted_at)),
+            ..Default::default()
+        };
+
+        let mut updates = vec![(level_id, update)];
+        updates.extend(references);
+        self.update_levels(updates, None).await
+    }
+
+    async fn delete_level(&self, level_id: LevelID) -> RepositoryResult<()> {
+        self.client
+            .delete_item()
+            .table_name(Level::TABLE)
+            .key(Level::PARTITION, AttributeValue::N(level_id.0.to_string()))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn publish_level(&self, snapshot: &PublishedLevel) -> RepositoryResult<bool> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(PublishedLevel::TABLE)
+            .set_item(Some(
+                to_item(snapshot)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .condition_expression("attribute_not_exists(#pk)")
+            .expression_attribute_names("#pk", PublishedLevel::PARTITION)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error());
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(PutItemError::ConditionalCheckFailedException(_)) => Ok(false),
+            Err(err) => Err(RepositoryError::Backend(Box::new(err))),
+        }
+    }
+
+    async fn get_published_levels(&self) -> RepositoryResult<Vec<PublishedLevel>> {
+        let snapshots: Vec<PublishedLevel> = self.scan_all(PublishedLevel::TABLE).await?;
+
+        Ok(snapshots
+            .into_iter()
+            .into_grouping_map_by(|snapshot| snapshot.level_id)
+            .max_by_key(|_, snapshot| snapshot.version)
+            .into_values()
+            .collect())
+    }
+
+    async fn get_published_level(&self, level_id: LevelID) -> RepositoryResult<PublishedLevel> {
+        self.client
+            .query()
+            .table_name(PublishedLevel::TABLE)
+            .key_condition_expression("#pk = :pk")
+            .expression_attribute_names("#pk", PublishedLevel::PARTITION)
+            .expression_attribute_values(":pk", AttributeValue::N(level_id.0.to_string()))
+            .scan_index_forward(false)
+            .limit(1)
+            .send()
+            .await
+            .box_error()
+            .and_then(|output| from_items(output.items.unwrap_or_default()).box_error())
+            .map_err(RepositoryError::Backend)?
+            .pop()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn delete_published_level(&self, level_id: LevelID) -> RepositoryResult<()> {
+        let versions: Vec<AttributeValue> = self
+            .client
+            .query()
+            .table_name(PublishedLevel::TABLE)
+            .key_condition_expression("#pk = :pk")
+            .projection_expression("#sk")
+            .expression_attribute_names("#pk", PublishedLevel::PARTITION)
+            .expression_attribute_names("#sk", PublishedLevel::SORT)
+            .expression_attribute_values(":pk", AttributeValue::N(level_id.0.to_string()))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .into_iter()
+            .filter_map(|mut item| item.remove(PublishedLevel::SORT))
+            .collect();
+
+        for version in versions {
+            self.client
+                .delete_item()
+                .table_name(PublishedLevel::TABLE)
+                .key(
+                    PublishedLevel::PARTITION,
+                    AttributeValue::N(level_id.0.to_string()),
+                )
+                .key(PublishedLevel::SORT, version)
+                .send()
+                .await
+                .box_error()
+                .map_err(RepositoryError::Backend)?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_templates(&self) -> RepositoryResult<Vec<PromptTemplate>> {
+        self.scan_all(PromptTemplate::TABLE).await
+    }
+
+    async fn get_template(&self, template_id: &TemplateID) -> RepositoryResult<PromptTemplate> {
+        let item = self
+            .client
+            .get_item()
+            .table_name(PromptTemplate::TABLE)
+            .key(
+                PromptTemplate::PARTITION,
+                AttributeValue::S(template_id.0.clone()),
+            )
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .item
+            .ok_or(RepositoryError::NotFound)?;
+
+        from_item(item)
+            .box_error()
+            .map_err(RepositoryError::Backend)
+    }
+
+    async fn put_template(&self, template: &PromptTemplate) -> RepositoryResult<()> {
+        self.client
+            .put_item()
+            .table_name(PromptTemplate::TABLE)
+            .set_item(Some(
+                to_item(template)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn update_template_name(
+        &self,
+        template_id: &TemplateID,
+        name: String,
+    ) -> RepositoryResult<()> {
+        self.update_existing(
+            PromptTemplate::TABLE,
+            PromptTemplate::PARTITION,
+            AttributeValue::S(template_id.0.clone()),
+            vec![(PromptTemplate::NAME, AttributeValue::S(name))],
+        )
+        .await
+    }
+
+    async fn delete_template(&self, template_id: &TemplateID) -> RepositoryResult<()> {
+        self.client
+            .delete_item()
+            .table_name(PromptTemplate::TABLE)
+            .key(
+                PromptTemplate::PARTITION,
+                AttributeValue::S(template_id.0.clone()),
+            )
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn get_components(
+        &self,
+        template_id: &TemplateID,
+    ) -> RepositoryResult<Vec<PromptComponent>> {
+        query_all(
+            self.client
+                .query()
+                .table_name(PromptComponent::TABLE)
+                .index_name(PromptComponent::SECONDARY_TEMPLATE_INDEX)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", PromptComponent::SECONDARY_TEMPLATE_ID)
+                .expression_attribute_values(":pk", AttributeValue::S(template_id.0.clone())),
+        )
+        .await
+    }
+
+    async fn get_component(&self, component_id: ComponentID) -> RepositoryResult<PromptComponent> {
+        let item = self
+            .client
+            .get_item()
+            .table_name(PromptComponent::TABLE)
+            .key(
+                PromptComponent::PARTITION,
+                AttributeValue::N(component_id.0.to_string()),
+            )
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .item
+            .ok_or(RepositoryError::NotFound)?;
+
+        from_item(item)
+            .box_error()
+            .map_err(RepositoryError::Backend)
+    }
+
+    async fn put_component(&self, component: &PromptComponent) -> RepositoryResult<()> {
+        self.client
+            .put_item()
+            .table_name(PromptComponent::TABLE)
+            .set_item(Some(
+                to_item(component)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn revise_component_text(
+        &self,
+        revision: NewComponentRevision,
+    ) -> RepositoryResult<ComponentRevision> {
+        for _ in 0..REVISION_ATTEMPTS {
+            if let Some(revision) = self.append_component_revision(&revision).await? {
+                return Ok(revision);
+            }
+        }
+
+        Err(RepositoryError::Backend(
+            format!(
+                "Concurrent edits of component {} kept conflicting",
+                revision.component_id.0
+            )
+            .into(),
+        ))
+    }
+
+    async fn get_component_revisions(
+        &self,
+        component_id: ComponentID,
+    ) -> RepositoryResult<Vec<ComponentRevision>> {
+        query_all(
+            self.client
+                .query()
+                .table_name(ComponentRevision::TABLE)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", ComponentRevision::PARTITION)
+                .expression_attribute_values(":pk", AttributeValue::N(component_id.0.to_string())),
+        )
+        .await
+    }
+
+    async fn delete_component_revisions(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        let revisions: Vec<AttributeValue> = self
+            .client
+            .query()
+            .table_name(ComponentRevision::TABLE)
+            .key_condition_expression("#pk = :pk")
+            .projection_expression("#sk")
+            .expression_attribute_names("#pk", ComponentRevision::PARTITION)
+            .expression_attribute_names("#sk", ComponentRevision::SORT)
+            .expression_attribute_values(":pk", AttributeValue::N(component_id.0.to_string()))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .into_iter()
+            .filter_map(|mut item| item.remove(ComponentRevision::SORT))
+            .collect();
+
+        for revision in revisions {
+            self.client
+                .delete_item()
+                .table_name(ComponentRevision::TABLE)
+                .key(
+                    ComponentRevision::PARTITION,
+                    AttributeValue::N(component_id.0.to_string()),
+                )
+                .key(ComponentRevision::SORT, revision)
+                .send()
+                .await
+                .box_error()
+                .map_err(RepositoryError::Backend)?;
+        }
+
+        Ok(())
+    }
+
+    async fn update_component_ordering(
+        &self,
+        component_id: ComponentID,
+        ordering: String,
+    ) -> RepositoryResult<()> {
+        self.update_existing(
+            PromptComponent::TABLE,
+            PromptComponent::PARTITION,
+            AttributeValue::N(component_id.0.to_string()),
+            vec![(
+                PromptComponent::SECONDARY_TEMPLATE_ORDERING,
+                AttributeValue::S(ordering),
+            )],
+        )
+        .await
+    }
+
+    async fn update_component_orderings(
+        &self,
+        template_id: &TemplateID,
+        orderings: Vec<(ComponentID, String)>,
+    ) -> RepositoryResult<()> {
+        if orderings.is_empty() {
+            return Ok(());
+        }
+        check_transaction_size(orderings.len())?;
+
+        let items = orderings
+            .into_iter()
+            .map(|(component_id, ordering)| {
+                let update = Update::builder()
+                    .table_name(PromptComponent::TABLE)
+                    .key(
+                        PromptComponent::PARTITION,
+                        AttributeValue::N(component_id.0.to_string()),
+                    )
+                    .update_expression("SET #ordering = :ordering")
+                    .condition_expression("#template = :template")
+                    .expression_attribute_names(
+                        "#ordering",
+                        PromptComponent::SECONDARY_TEMPLATE_ORDERING,
+                    )
+                    .expression_attribute_names("#template", PromptComponent::SECONDARY_TEMPLATE_ID)
+                    .expression_attribute_values(":ordering", AttributeValue::S(ordering))
+                    .expression_attribute_values(
+                        ":template",
+                        AttributeValue::S(template_id.0.clone()),
+                    )
+                    .build()
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?;
+                Ok(TransactWriteItem::builder().update(update).build())
+            })
+            .collect::<RepositoryResult<Vec<_>>>()?;
+
+        let result = self
+            .client
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .send()
+            .await
+            .map_err(|err| err.into_service_error());
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(TransactWriteItemsError::TransactionCanceledException(err))
+                if err
+                    .cancellation_reasons()
+                    .iter()
+                    .any(|reason| reason.code() == Some("ConditionalCheckFailed")) =>
+            {
+                Err(RepositoryError::NotFound)
+            }
+            Err(err) => Err(RepositoryError::Backend(Box::new(err))),
+        }
+    }
+
+    async fn delete_component(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        self.client
+            .delete_item()
+            .table_name(PromptComponent::TABLE)
+            .key(
+                PromptComponent::PARTITION,
+                AttributeValue::N(component_id.0.to_string()),
+            )
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn trash_component(
+        &self,
+        component_id: ComponentID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()> {
+        let trash = existing_item_update(
+            PromptComponent::TABLE,
+            PromptComponent::PARTITION,
+            AttributeValue::N(component_id.0.to_string()),
+            vec![(
+                PromptComponent::DELETED_AT,
+                AttributeValue::N(deleted_at.to_string()),
+            )],
+        )?;
+
+        self.update_levels(references, Some(trash)).await
+    }
+
+    async fn restore_component(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        self.update_existing(
+            PromptComponent::TABLE,
+            PromptComponent::PARTITION,
+            AttributeValue::N(component_id.0.to_string()),
+            vec![(PromptComponent::DELETED_AT, AttributeValue::Null(true))],
+        )
+        .await
+    }
+
+    async fn get_all_components(&self) -> RepositoryResult<Vec<PromptComponent>> {
+        self.scan_all(PromptComponent::TABLE).await
+    }
+
+    async fn record_chat_turn(&self, turn: NewChatTurn) -> RepositoryResult<()> {
+        #[derive(Deserialize)]
+        struct TurnCount {
+            turn_count: u64,
+        }
+
+        // The session is created with its first turn, later turns only update its activity
+        let mut update_expression = String::from(
+            "SET #client_session_id = if_not_exists(#client_session_id, :client_session_id), \
+            #level_id = if_not_exists(#level_id, :level_id), \
+            #username = if_not_exists(#username, :username), #updated_at = :timestamp, \
+            #started_at = if_not_exists(#started_at, :timestamp)",
+        );
+        if turn.leak.is_some() {
+            update_expression
+                .push_str(", #first_leak_at = if_not_exists(#first_leak_at, :timestamp)");
+        }
+        update_expression.push_str(" ADD #turn_count :one, #leak_count :leaks");
+
+        let mut request = self
+            .client
+            .update_item()
+            .table_name(ChatSession::TABLE)
+            .key(
+                ChatSession::PARTITION,
+                AttributeValue::S(turn.session_id.clone()),
+            )
+            .update_expression(update_expression)
+            .expression_attribute_names("#client_session_id", ChatSession::CLIENT_SESSION_ID)
+            .expression_attribute_names("#level_id", ChatSession::LEVEL_ID)
+            .expression_attribute_names("#username", ChatSession::SECONDARY_USERNAME)
+            .expression_attribute_names("#updated_at", ChatSession::SECONDARY_USERNAME_UPDATED_AT)
+            .expression_attribute_names("#started_at", ChatSession::STARTED_AT)
+            .expression_attribute_names("#turn_count", ChatSession::TURN_COUNT)
+            .expression_attribute_names("#leak_count", ChatSession::LEAK_COUNT)
+            .expression_attribute_values(
+                ":client_session_id",
+                AttributeValue::S(turn.client_session_id),
+            )
+            .expression_attribute_values(
+                ":level_id",
+                AttributeValue::N(turn.level_id.0.to_string()),
+            )
+            .expression_attribute_values(":username", AttributeValue::S(turn.username))
+            .expression_attribute_values(
+                ":timestamp",
+                AttributeValue::N(turn.timestamp.to_string()),
+            )
+            .expression_attribute_values(":one", AttributeValue::N("1".into()))
+            .expression_attribute_values(
+                ":leaks",
+                AttributeValue::N(u64::from(turn.leak.is_some()).to_string()),
+            );
+        if turn.leak.is_some() {
+            request =
+                request.expression_attribute_names("#first_leak_at", ChatSession::FIRST_LEAK_AT);
+        }
+
+        let TurnCount { turn_count } = request
+            .return_values(ReturnValue::UpdatedNew)
+            .send()
+            .await
+            .box_error()
+            .and_then(|output| {
+                from_item(output.attributes.expect("No attributes returned")).box_error()
+            })
+            .map_err(RepositoryError::Backend)?;
+
+        let chat_turn = ChatTurn {
+            session_id: turn.session_id,
+            turn: turn_count - 1,
+            message: turn.message,
+            reply: turn.reply,
+            timestamp: turn.timestamp,
+            leak: turn.leak,
+        };
+
+        self.client
+            .put_item()
+            .table_name(ChatTurn::TABLE)
+            .set_item(Some(
+                to_item(chat_turn)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(())
+    }
+
+    async fn get_chat_sessions(&self, username: &str) -> RepositoryResult<Vec<ChatSession>> {
+        query_all(
+            self.client
+                .query()
+                .table_name(ChatSession::TABLE)
+                .index_name(ChatSession::SECONDARY_USERNAME_INDEX)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", ChatSession::SECONDARY_USERNAME)
+                .expression_attribute_values(":pk", AttributeValue::S(username.to_owned()))
+                .scan_index_forward(false),
+        )
+        .await
+    }
+
+    async fn get_chat_session(&self, session_id: &str) -> RepositoryResult<ChatSession> {
+        let item = self
+            .client
+            .get_item()
+            .table_name(ChatSession::TABLE)
+            .key(
+                ChatSession::PARTITION,
+                AttributeValue::S(session_id.to_owned()),
+            )
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?
+            .item
+            .ok_or(RepositoryError::NotFound)?;
+
+        from_item(item)
+            .box_error()
+            .map_err(RepositoryError::Backend)
+    }
+
+    async fn scan_chat_sessions(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> RepositoryResult<ChatSessionPage> {
+        let output = self
+            .client
+            .scan()
+            .table_name(ChatSession::TABLE)
+            .limit(i32::try_from(limit).unwrap_or(i32::MAX))
+            .set_exclusive_start_key(after.map(|after| {
+                [(
+                    ChatSession::PARTITION.to_owned(),
+                    AttributeValue::S(after.to_owned()),
+                )]
+                .into()
+            }))
+            .send()
+            .await
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        let next = output
+            .last_evaluated_key()
+            .and_then(|key| key.get(ChatSession::PARTITION))
+            .and_then(|key| key.as_s().ok())
+            .cloned();
+        let sessions = from_items(output.items.unwrap_or_default())
+            .box_error()
+            .map_err(RepositoryError::Backend)?;
+
+        Ok(ChatSessionPage { sessions, next })
+    }
+
+    async fn get_chat_turns(&self, session_id: &str) -> RepositoryResult<Vec<ChatTurn>> {
+        query_all(
+            self.client
+                .query()
+                .table_name(ChatTurn::TABLE)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", ChatTurn::PARTITION)
+                .expression_attribute_values(":pk", AttributeValue::S(session_id.to_owned())),
+        )
+        .await
+    }
+
+    async fn record_level_solve(&self, solve: LevelSolve) -> RepositoryResult<()> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(LevelSolve::TABLE)
+            .set_item(Some(
+                to_item(solve)
+                    .box_error()
+                    .map_err(RepositoryError::Backend)?,
+            ))
+            .condition_expression("attribute_not_exists(#pk)")
+            .expression_attribute_names("#pk", LevelSolve::PARTITION)
+            .send()
+            .await
+            .map_err(|err| err.into_service_error());
+
+        match result {
+            Ok(_) | Err(PutItemError::ConditionalCheckFailedException(_)) => Ok(()),
+            Err(err) => Err(RepositoryError::Backend(Box::new(err))),
+        }
+    }
+
+    async fn get_level_solves(&self, username: &str) -> RepositoryResult<Vec<LevelSolve>> {
+        query_all(
+            self.client
+                .query()
+                .table_name(LevelSolve::TABLE)
+                .key_condition_expression("#pk = :pk")
+                .expression_attribute_names("#pk", LevelSolve::PARTITION)
+                .expression_attribute_values(":pk", AttributeValue::S(username.to_owned())),
+        )
+        .await
+    }
+}
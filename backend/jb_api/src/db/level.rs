@@ -1,24 +1,86 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct LevelID(pub u64);
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Ordered from easiest to hardest
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum LevelDifficulty {
     Low,
     Medium,
     High,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Model and inference parameters used for chats on a level
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct ModelSettings {
+    /// Model as `provider:model_id`, the deployment's default model is used if unset
+    pub model: Option<String>,
+    pub temperature: f32,
+    /// Maximum length of a reply in tokens
+    pub maximum_length: u32,
+    pub stop_sequences: Vec<String>,
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        Self {
+            model: None,
+            temperature: 0.9,
+            maximum_length: 150,
+            stop_sequences: vec!["</answer>".to_owned()],
+        }
+    }
+}
+
+/// Part of a level's prompt layout
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromptBlock {
+    /// Shared component of the level's template
+    Component { component_id: super::ComponentID },
+    /// Text only used by this level
+    Text { text: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Level {
     pub level_id: LevelID,
     pub name: String,
     pub password: String,
     pub difficulty: LevelDifficulty,
+    #[serde(default)]
+    pub template_id: super::TemplateID,
+    /// Components of the level's template making up its prompt
     pub prompt_components: Vec<super::ComponentID>,
+    /// Blocks of the prompt in order, referencing exactly the components in `prompt_components`
+    ///
+    /// Empty for levels predating layouts, whose components are put in template order.
+    #[serde(default)]
+    pub prompt_layout: Vec<PromptBlock>,
     pub is_root: bool,
     pub next: Vec<LevelID>,
+    /// Playable without solving a preceding level, like a root, but not listed as an entry point
+    #[serde(default)]
+    pub is_open: bool,
+    #[serde(default)]
+    pub model_settings: ModelSettings,
+    /// Count a reply leaking the password as a solve, so the player advances without validating it
+    #[serde(default)]
+    pub solve_on_leak: bool,
+    #[serde(default)]
+    pub input_guards: Vec<super::InputGuard>,
+    #[serde(default)]
+    pub output_guards: Vec<super::OutputGuard>,
+    /// Custom variables available to the level's prompt components by name
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// When the level was moved to the trash, hiding it from players and the level list
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
 }
 
 impl Level {
@@ -28,7 +90,105 @@ impl Level {
     pub const NAME: &'static str = "name";
     pub const PASSWORD: &'static str = "password";
     pub const DIFFICULTY: &'static str = "difficulty";
+    pub const TEMPLATE_ID: &'static str = "template_id";
     pub const PROMPT_COMPONENTS: &'static str = "prompt_components";
+    pub const PROMPT_LAYOUT: &'static str = "prompt_layout";
     pub const IS_ROOT: &'static str = "is_root";
     pub const NEXT: &'static str = "next";
+    pub const IS_OPEN: &'static str = "is_open";
+    pub const MODEL_SETTINGS: &'static str = "model_settings";
+    pub const SOLVE_ON_LEAK: &'static str = "solve_on_leak";
+    pub const INPUT_GUARDS: &'static str = "input_guards";
+    pub const OUTPUT_GUARDS: &'static str = "output_guards";
+    pub const VARIABLES: &'static str = "variables";
+    pub const DELETED_AT: &'static str = "deleted_at";
+}
+
+/// Partial update of a [`Level`], fields set to `None` are left untouched
+#[derive(Default, Debug)]
+pub struct LevelUpdate {
+    pub name: Option<String>,
+    pub password: Option<String>,
+    pub difficulty: Option<LevelDifficulty>,
+    pub template_id: Option<super::TemplateID>,
+    pub prompt_components: Option<Vec<super::ComponentID>>,
+    pub prompt_layout: Option<Vec<PromptBlock>>,
+    pub is_root: Option<bool>,
+    pub next: Option<Vec<LevelID>>,
+    pub is_open: Option<bool>,
+    pub model_settings: Option<ModelSettings>,
+    pub solve_on_leak: Option<bool>,
+    pub input_guards: Option<Vec<super::InputGuard>>,
+    pub output_guards: Option<Vec<super::OutputGuard>>,
+    pub variables: Option<BTreeMap<String, String>>,
+    /// `Some(None)` restores the level from the trash
+    pub deleted_at: Option<Option<u64>>,
+}
+
+impl LevelUpdate {
+    pub fn apply(self, level: &mut Level) {
+        let LevelUpdate {
+            name,
+            password,
+            difficulty,
+            template_id,
+            prompt_components,
+            prompt_layout,
+            is_root,
+            next,
+            is_open,
+            model_settings,
+            solve_on_leak,
+            input_guards,
+            output_guards,
+            variables,
+            deleted_at,
+        } = self;
+
+        if let Some(name) = name {
+            level.name = name;
+        }
+        if let Some(password) = password {
+            level.password = password;
+        }
+        if let Some(difficulty) = difficulty {
+            level.difficulty = difficulty;
+        }
+        if let Some(template_id) = template_id {
+            level.template_id = template_id;
+        }
+        if let Some(prompt_components) = prompt_components {
+            level.prompt_components = prompt_components;
+        }
+        if let Some(prompt_layout) = prompt_layout {
+            level.prompt_layout = prompt_layout;
+        }
+        if let Some(is_root) = is_root {
+            level.is_root = is_root;
+        }
+        if let Some(next) = next {
+            level.next = next;
+        }
+        if let Some(is_open) = is_open {
+            level.is_open = is_open;
+        }
+        if let Some(model_settings) = model_settings {
+            level.model_settings = model_settings;
+        }
+        if let Some(solve_on_leak) = solve_on_leak {
+            level.solve_on_leak = solve_on_leak;
+        }
+        if let Some(input_guards) = input_guards {
+            level.input_guards = input_guards;
+        }
+        if let Some(output_guards) = output_guards {
+            level.output_guards = output_guards;
+        }
+        if let Some(variables) = variables {
+            level.variables = variables;
+        }
+        if let Some(deleted_at) = deleted_at {
+            level.deleted_at = deleted_at;
+        }
+    }
 }
@@ -0,0 +1,471 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use itertools::Itertools;
+
+use super::*;
+
+#[derive(Default)]
+struct Tables {
+    counters: HashMap<&'static str, u64>,
+    levels: BTreeMap<LevelID, Level>,
+    published_levels: BTreeMap<(LevelID, u64), PublishedLevel>,
+    templates: HashMap<TemplateID, PromptTemplate>,
+    components: BTreeMap<ComponentID, PromptComponent>,
+    component_revisions: HashMap<ComponentID, Vec<ComponentRevision>>,
+    chat_sessions: HashMap<String, ChatSession>,
+    chat_turns: HashMap<String, Vec<ChatTurn>>,
+    level_solves: HashMap<(String, LevelID), LevelSolve>,
+}
+
+/// Repository keeping all data in process memory, intended for tests and local development
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Apply all updates, or none if any of the levels does not exist
+fn apply_level_updates(
+    levels: &mut BTreeMap<LevelID, Level>,
+    updates: Vec<(LevelID, LevelUpdate)>,
+) -> RepositoryResult<()> {
+    if !updates
+        .iter()
+        .all(|(level_id, _)| levels.contains_key(level_id))
+    {
+        return Err(RepositoryError::NotFound);
+    }
+
+    for (level_id, update) in updates {
+        if let Some(level) = levels.get_mut(&level_id) {
+            update.apply(level);
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Repository for MemoryRepository {
+    async fn increment_counter(&self, counter: &'static str) -> RepositoryResult<u64> {
+        let mut tables = self.tables();
+        let count = tables.counters.entry(counter).or_default();
+        *count += 1;
+        Ok(*count)
+    }
+
+    async fn get_levels(&self) -> RepositoryResult<Vec<Level>> {
+        Ok(self.tables().levels.values().cloned().collect())
+    }
+
+    async fn get_level(&self, level_id: LevelID) -> RepositoryResult<Level> {
+        self.tables()
+            .levels
+            .get(&level_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn put_level(&self, level: &Level) -> RepositoryResult<()> {
+        self.tables().levels.insert(level.level_id, level.clone());
+        Ok(())
+    }
+
+    async fn update_level(&self, level_id: LevelID, update: LevelUpdate) -> RepositoryResult<()> {
+        let mut tables = self.tables();
+        let level = tables
+            .levels
+            .get_mut(&level_id)
+            .ok_or(RepositoryError::NotFound)?;
+        update.apply(level);
+        Ok(())
+    }
+
+    async fn delete_level(&self, level_id: LevelID) -> RepositoryResult<()> {
+        self.tables().levels.remove(&level_id);
+        Ok(())
+    }
+
+    async fn trash_level(
+        &self,
+        level_id: LevelID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()> {
+        let update = LevelUpdate {
+            deleted_at: Some(Some(deleted_at)),
+            ..Default::default()
+        };
+
+        let mut updates = vec![(level_id, update)];
+        updates.extend(references);
+        check_transaction_size(updates.len())?;
+        apply_level_updates(&mut self.tables().levels, updates)
+    }
+
+    async fn publish_level(&self, snapshot: &PublishedLevel) -> RepositoryResult<bool> {
+        let mut tables = self.tables();
+        let key = (snapshot.level_id, snapshot.version);
+        if tables.published_levels.contains_key(&key) {
+            return Ok(false);
+        }
+        tables.published_levels.insert(key, snapshot.clone());
+        Ok(true)
+    }
+
+    async fn get_published_levels(&self) -> RepositoryResult<Vec<PublishedLevel>> {
+        Ok(self
+            .tables()
+            .published_levels
+            .values()
+            .rev()
+            .unique_by(|snapshot| snapshot.level_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_published_level(&self, level_id: LevelID) -> RepositoryResult<PublishedLevel> {
+        self.tables()
+            .published_levels
+            .range((level_id, 0)..=(level_id, u64::MAX))
+            .next_back()
+            .map(|(_, snapshot)| snapshot.clone())
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn delete_published_level(&self, level_id: LevelID) -> RepositoryResult<()> {
+        self.tables()
+            .published_levels
+            .retain(|(published_id, _), _| *published_id != level_id);
+        Ok(())
+    }
+
+    async fn get_templates(&self) -> RepositoryResult<Vec<PromptTemplate>> {
+        Ok(self.tables().templates.values().cloned().collect())
+    }
+
+    async fn get_template(&self, template_id: &TemplateID) -> RepositoryResult<PromptTemplate> {
+        self.tables()
+            .templates
+            .get(template_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn put_template(&self, template: &PromptTemplate) -> RepositoryResult<()> {
+        self.tables()
+            .templates
+            .insert(template.template_id.clone(), template.clone());
+        Ok(())
+    }
+
+    async fn update_template_name(
+        &self,
+        template_id: &TemplateID,
+        name: String,
+    ) -> RepositoryResult<()> {
+        let mut tables = self.tables();
+        let template = tables
+            .templates
+            .get_mut(template_id)
+            .ok_or(RepositoryError::NotFound)?;
+        template.name = name;
+        Ok(())
+    }
+
+    async fn delete_template(&self, template_id: &TemplateID) -> RepositoryResult<()> {
+        self.tables().templates.remove(template_id);
+        Ok(())
+    }
+
+    async fn get_components(
+        &self,
+        template_id: &TemplateID,
+    ) -> RepositoryResult<Vec<PromptComponent>> {
+        Ok(self
+            .tables()
+            .components
+            .values()
+            .filter(|component| component.template_id == *template_id)
+            .sorted_by(|a, b| a.ordering.cmp(&b.ordering))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_component(&self, component_id: ComponentID) -> RepositoryResult<PromptComponent> {
+        self.tables()
+            .components
+            .get(&component_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn put_component(&self, component: &PromptComponent) -> RepositoryResult<()> {
+        self.tables()
+            .components
+            .insert(component.component_id, component.clone());
+        Ok(())
+    }
+
+    async fn revise_component_text(
+        &self,
+        revision: NewComponentRevision,
+    ) -> RepositoryResult<ComponentRevision> {
+        let mut tables = self.tables();
+        let Tables {
+            components,
+            component_revisions,
+            ..
+        } = &mut *tables;
+
+        let component = components
+            .get_mut(&revision.component_id)
+            .ok_or(RepositoryError::NotFound)?;
+        let history = component_revisions
+            .entry(revision.component_id)
+            .or_default();
+
+        if history.is_empty() {
+            history.push(ComponentRevision {
+                component_id: revision.component_id,
+                revision: 0,
+                author: None,
+                timestamp: revision.timestamp,
+                text: component.text.clone(),
+                restored_from: None,
+            });
+        }
+
+        let revision = ComponentRevision {
+            component_id: revision.component_id,
+            revision: history.len() as u64,
+            author: Some(revision.author),
+            timestamp: revision.timestamp,
+            text: revision.text,
+            restored_from: revision.restored_from,
+        };
+        component.text = revision.text.clone();
+        history.push(revision.clone());
+
+        Ok(revision)
+    }
+
+    async fn get_component_revisions(
+        &self,
+        component_id: ComponentID,
+    ) -> RepositoryResult<Vec<ComponentRevision>> {
+        Ok(self
+            .tables()
+            .component_revisions
+            .get(&component_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn delete_component_revisions(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        self.tables().component_revisions.remove(&component_id);
+        Ok(())
+    }
+
+    async fn update_component_ordering(
+        &self,
+        component_id: ComponentID,
+        ordering: String,
+    ) -> RepositoryResult<()> {
+        let mut tables = self.tables();
+        let component = tables
+            .components
+            .get_mut(&component_id)
+            .ok_or(RepositoryError::NotFound)?;
+        component.ordering = ordering;
+        Ok(())
+    }
+
+    async fn update_component_orderings(
+        &self,
+        template_id: &TemplateID,
+        orderings: Vec<(ComponentID, String)>,
+    ) -> RepositoryResult<()> {
+        check_transaction_size(orderings.len())?;
+
+        let mut tables = self.tables();
+        if !orderings.iter().all(|(component_id, _)| {
+            tables
+                .components
+                .get(component_id)
+                .is_some_and(|component| component.template_id == *template_id)
+        }) {
+            return Err(RepositoryError::NotFound);
+        }
+
+        for (component_id, ordering) in orderings {
+            if let Some(component) = tables.components.get_mut(&component_id) {
+                component.ordering = ordering;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_component(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        self.tables().components.remove(&component_id);
+        Ok(())
+    }
+
+    async fn trash_component(
+        &self,
+        component_id: ComponentID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()> {
+        check_transaction_size(references.len() + 1)?;
+
+        let mut tables = self.tables();
+        if !tables.components.contains_key(&component_id) {
+            return Err(RepositoryError::NotFound);
+        }
+        apply_level_updates(&mut tables.levels, references)?;
+        if let Some(component) = tables.components.get_mut(&component_id) {
+            component.deleted_at = Some(deleted_at);
+        }
+        Ok(())
+    }
+
+    async fn restore_component(&self, component_id: ComponentID) -> RepositoryResult<()> {
+        let mut tables = self.tables();
+        let component = tables
+            .components
+            .get_mut(&component_id)
+            .ok_or(RepositoryError::NotFound)?;
+        component.deleted_at = None;
+        Ok(())
+    }
+
+    async fn get_all_components(&self) -> RepositoryResult<Vec<PromptComponent>> {
+        Ok(self.tables().components.values().cloned().collect())
+    }
+
+    async fn record_chat_turn(&self, turn: NewChatTurn) -> RepositoryResult<()> {
+        let mut tables = self.tables();
+
+        let session = tables
+            .chat_sessions
+            .entry(turn.session_id.clone())
+            .or_insert_with(|| ChatSession {
+                session_id: turn.session_id.clone(),
+                client_session_id: turn.client_session_id.clone(),
+                level_id: turn.level_id,
+                username: turn.username.clone(),
+                started_at: turn.timestamp,
+                updated_at: turn.timestamp,
+                turn_count: 0,
+                first_leak_at: None,
+                leak_count: 0,
+            });
+        session.updated_at = turn.timestamp;
+        session.turn_count += 1;
+        if turn.leak.is_some() {
+            session.first_leak_at.get_or_insert(turn.timestamp);
+            session.leak_count += 1;
+        }
+        let turn_index = session.turn_count - 1;
+
+        tables
+            .chat_turns
+            .entry(turn.session_id.clone())
+            .or_default()
+            .push(ChatTurn {
+                session_id: turn.session_id,
+                turn: turn_index,
+                message: turn.message,
+                reply: turn.reply,
+                timestamp: turn.timestamp,
+                leak: turn.leak,
+            });
+        Ok(())
+    }
+
+    async fn get_chat_sessions(&self, username: &str) -> RepositoryResult<Vec<ChatSession>> {
+        Ok(self
+            .tables()
+            .chat_sessions
+            .values()
+            .filter(|session| session.username == username)
+            .sorted_by(|a, b| b.updated_at.cmp(&a.updated_at))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_chat_session(&self, session_id: &str) -> RepositoryResult<ChatSession> {
+        self.tables()
+            .chat_sessions
+            .get(session_id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound)
+    }
+
+    async fn scan_chat_sessions(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> RepositoryResult<ChatSessionPage> {
+        let mut sessions = self
+            .tables()
+            .chat_sessions
+            .values()
+            .filter(|session| after.is_none_or(|after| session.session_id.as_str() > after))
+            .sorted_by(|a, b| a.session_id.cmp(&b.session_id))
+            .cloned()
+            .collect_vec();
+
+        let next = if sessions.len() > limit {
+            sessions.truncate(limit);
+            sessions.last().map(|session| session.session_id.clone())
+        } else {
+            None
+        };
+
+        Ok(ChatSessionPage { sessions, next })
+    }
+
+    async fn get_chat_turns(&self, session_id: &str) -> RepositoryResult<Vec<ChatTurn>> {
+        Ok(self
+            .tables()
+            .chat_turns
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn record_level_solve(&self, solve: LevelSolve) -> RepositoryResult<()> {
+        self.tables()
+            .level_solves
+            .entry((solve.username.clone(), solve.level_id))
+            .or_insert(solve);
+        Ok(())
+    }
+
+    async fn get_level_solves(&self, username: &str) -> RepositoryResult<Vec<LevelSolve>> {
+        Ok(self
+            .tables()
+            .level_solves
+            .values()
+            .filter(|solve| solve.username == username)
+            .sorted_by_key(|solve| solve.level_id)
+            .cloned()
+            .collect())
+    }
+}
@@ -1,7 +1,31 @@
 mod counter;
+mod dynamo;
+mod guard;
 mod level;
+mod memory;
 mod prompt;
+mod published;
+mod repository;
+mod revision;
+mod solve;
+mod transcript;
 
 pub use counter::*;
+pub use dynamo::*;
+pub use guard::*;
 pub use level::*;
+pub use memory::*;
 pub use prompt::*;
+pub use published::*;
+pub use repository::*;
+pub use revision::*;
+pub use solve::*;
+pub use transcript::*;
+
+/// Current time in seconds since the unix epoch
+pub fn timestamp_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("System time is before the unix epoch")
+        .as_secs()
+}
@@ -1,12 +1,8 @@
-use std::collections::HashMap;
-
-use aws_sdk_dynamodb::types::AttributeValue;
-use axum::BoxError;
 use serde::{Deserialize, Serialize};
 
-use crate::{db, response::MapBoxError};
+use super::{MAX_TRANSACTION_ITEMS, Repository, RepositoryResult};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct TemplateID(pub String);
 
 impl Default for TemplateID {
@@ -15,15 +11,52 @@ impl Default for TemplateID {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+impl TemplateID {
+    pub fn from_counter(count: u64) -> Self {
+        Self(format!("template-{count}"))
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Named collection of prompt components, levels assemble their prompt from a single template
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PromptTemplate {
+    pub template_id: TemplateID,
+    pub name: String,
+    pub created_at: u64,
+}
+
+impl PromptTemplate {
+    pub const TABLE: &'static str = "jb_templates";
+    pub const PARTITION: &'static str = "template_id";
+
+    pub const NAME: &'static str = "name";
+
+    /// The default template predates named templates and exists even without being stored
+    pub fn default_template() -> Self {
+        Self {
+            template_id: TemplateID::default(),
+            name: "Default".to_owned(),
+            created_at: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct ComponentID(pub u64);
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PromptComponent {
     pub component_id: ComponentID,
     pub template_id: TemplateID,
     pub ordering: String,
     pub text: String,
+    /// When the component was moved to the trash, hiding it from the component list
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
 }
 
 impl PromptComponent {
@@ -35,81 +68,308 @@ impl PromptComponent {
     pub const SECONDARY_TEMPLATE_ORDERING: &'static str = "ordering";
 
     pub const TEXT: &'static str = "text";
+    pub const DELETED_AT: &'static str = "deleted_at";
 
+    /// Keys growing longer than this make [`PromptComponent::create_sort_key`]
+    /// rebalance the template before placing the component
+    pub const MAX_SORT_KEY_LENGTH: usize = 16;
+
+    /// Sort key placing a component right after `predecessor`, or first if there is none
+    ///
+    /// Returns `None` if the predecessor is not a component of the template.
     pub async fn create_sort_key(
-        client: &aws_sdk_dynamodb::Client,
+        repository: &dyn Repository,
+        template_id: &TemplateID,
         predecessor: Option<ComponentID>,
-    ) -> Result<Option<String>, BoxError> {
-        fn into_ordering(item: &HashMap<String, AttributeValue>) -> Result<String, BoxError> {
-            item[db::PromptComponent::SECONDARY_TEMPLATE_ORDERING]
-                .as_s()
-                .cloned()
-                .map_err(|err| {
-                    BoxError::from(format!(
-                        "Prompt template ordering was not a string but {err:?}"
-                    ))
-                })
+    ) -> RepositoryResult<Option<String>> {
+        let components = repository.get_components(template_id).await?;
+        let Some(key) = Self::sort_key_after(&components, predecessor) else {
+            return Ok(None);
+        };
+
+        if key.len() <= Self::MAX_SORT_KEY_LENGTH {
+            return Ok(Some(key));
+        }
+
+        match Self::rebalance(repository, template_id).await? {
+            Some(components) => Ok(Self::sort_key_after(&components, predecessor)),
+            // Too large to rebalance, the long key still sorts correctly
+            None => Ok(Some(key)),
+        }
+    }
+
+    /// Rewrite the ordering keys of all components of a template to short, evenly spaced ones,
+    /// keeping their order, and return the components with their new keys
+    ///
+    /// Returns `None` without changing anything if the template has more components than
+    /// can be updated at once.
+    pub async fn rebalance(
+        repository: &dyn Repository,
+        template_id: &TemplateID,
+    ) -> RepositoryResult<Option<Vec<PromptComponent>>> {
+        let mut components = repository.get_components(template_id).await?;
+        if components.len() > MAX_TRANSACTION_ITEMS {
+            return Ok(None);
+        }
+        if components.is_empty() {
+            return Ok(Some(components));
+        }
+
+        let orderings = Self::create_balanced_sort_keys(components.len());
+        for (component, ordering) in components.iter_mut().zip(orderings) {
+            component.ordering = ordering;
         }
 
-        let components = client
-            .query()
-            .table_name(db::PromptComponent::TABLE)
-            .index_name(db::PromptComponent::SECONDARY_TEMPLATE_INDEX)
-            .key_condition_expression("#pk = :pk")
-            .expression_attribute_names("#pk", db::PromptComponent::SECONDARY_TEMPLATE_ID)
-            .expression_attribute_values(":pk", AttributeValue::S(db::TemplateID::default().0))
-            .projection_expression(
-                [
-                    db::PromptComponent::PARTITION,
-                    ",",
-                    db::PromptComponent::SECONDARY_TEMPLATE_ORDERING,
-                ]
-                .concat(),
+        repository
+            .update_component_orderings(
+                template_id,
+                components
+                    .iter()
+                    .map(|component| (component.component_id, component.ordering.clone()))
+                    .collect(),
             )
-            .send()
-            .await
-            .box_error()?
-            .items
-            .unwrap_or_default();
+            .await?;
+
+        Ok(Some(components))
+    }
 
+    fn sort_key_after(
+        components: &[PromptComponent],
+        predecessor: Option<ComponentID>,
+    ) -> Option<String> {
         let (mut pred_ordering, mut succ_ordering) = (None, None);
 
         if let Some(predecessor) = predecessor {
             let mut components = components.iter();
 
-            let Some(predecessor) = components.find(|component| {
-                component.get(db::PromptComponent::PARTITION)
-                    == Some(&AttributeValue::N(predecessor.0.to_string()))
-            }) else {
-                return Ok(None);
-            };
+            let predecessor = components.find(|component| component.component_id == predecessor)?;
 
-            pred_ordering = Some(into_ordering(predecessor)?);
-            succ_ordering = components.next().map(into_ordering).transpose()?;
+            pred_ordering = Some(predecessor.ordering.as_str());
+            succ_ordering = components
+                .next()
+                .map(|component| component.ordering.as_str());
         } else if let Some(first) = components.first() {
-            succ_ordering = Some(into_ordering(first)?)
+            succ_ordering = Some(first.ordering.as_str())
         }
 
-        Ok(Some(db::PromptComponent::create_sort_key_between(
-            pred_ordering.as_deref(),
-            succ_ordering.as_deref(),
-        )))
+        Some(PromptComponent::create_sort_key_between(
+            pred_ordering,
+            succ_ordering,
+        ))
     }
 
     /// Given two lexicographically sorted keys produced by this function,
     /// return a new key, whose sorting order is guaranteed to be between the provided keys
+    ///
+    /// Keys are fractions in base 62 whose digits follow the first `.` of the number,
+    /// so a key one digit longer than its neighbours always fits between them.
+    /// Keys never end in the smallest digit, which would leave no room right before them.
     fn create_sort_key_between(predecessor: Option<&str>, successor: Option<&str>) -> String {
-        let predecessor = predecessor.unwrap_or("");
+        let predecessor = predecessor.unwrap_or("").as_bytes();
+        let successor = successor.map(str::as_bytes);
 
-        let mut between = [predecessor, "1"].concat();
+        let key = Self::midpoint(predecessor, successor);
+        String::from_utf8(key).expect("Sort key digits are ASCII")
+    }
 
+    fn midpoint(predecessor: &[u8], successor: Option<&[u8]>) -> Vec<u8> {
         if let Some(successor) = successor {
-            while *between >= *successor {
-                assert!(between.len() < 100000, "Prompt component sort key overflow");
-                between.insert(between.len() - 1, '0');
+            let common = successor
+                .iter()
+                .enumerate()
+                .take_while(|&(i, digit)| {
+                    predecessor.get(i).unwrap_or(&SORT_KEY_DIGITS[0]) == digit
+                })
+                .count();
+
+            if common > 0 {
+                let mut key = successor[..common].to_vec();
+                key.extend(Self::midpoint(
+                    predecessor.get(common..).unwrap_or_default(),
+                    Some(&successor[common..]),
+                ));
+                return key;
             }
         }
 
-        between
+        let low = predecessor
+            .first()
+            .map_or(0, |digit| sort_key_digit(*digit));
+        let high = successor
+            .and_then(|successor| successor.first())
+            .map_or(SORT_KEY_DIGITS.len(), |digit| sort_key_digit(*digit));
+
+        if high - low > 1 {
+            vec![SORT_KEY_DIGITS[(low + high).div_ceil(2)]]
+        } else if let Some(successor) = successor.filter(|successor| successor.len() > 1) {
+            successor[..1].to_vec()
+        } else {
+            let mut key = vec![SORT_KEY_DIGITS[low]];
+            key.extend(Self::midpoint(
+                predecessor.get(1..).unwrap_or_default(),
+                None,
+            ));
+            key
+        }
+    }
+
+    /// `count` sorted keys of at most the same length, evenly spread over the key space
+    pub fn create_balanced_sort_keys(count: usize) -> Vec<String> {
+        let base = SORT_KEY_DIGITS.len();
+        let (mut length, mut capacity) = (1, base);
+        while capacity <= count {
+            length += 1;
+            capacity *= base;
+        }
+
+        let step = capacity / (count + 1);
+        (1..=count)
+            .map(|position| {
+                let mut value = position * step;
+                let mut key = vec![SORT_KEY_DIGITS[0]; length];
+                for digit in key.iter_mut().rev() {
+                    *digit = SORT_KEY_DIGITS[value % base];
+                    value /= base;
+                }
+                while key.last() == Some(&SORT_KEY_DIGITS[0]) {
+                    key.pop();
+                }
+                String::from_utf8(key).expect("Sort key digits are ASCII")
+            })
+            .collect()
+    }
+}
+
+/// Digits of sort keys in ascending order
+const SORT_KEY_DIGITS: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn sort_key_digit(digit: u8) -> usize {
+    SORT_KEY_DIGITS
+        .iter()
+        .position(|candidate| *candidate == digit)
+        .expect("Sort keys only contain base 62 digits")
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    enum Operation {
+        /// Add a component after the one at the index, modulo the number of components
+        Insert {
+            predecessor: Option<usize>,
+        },
+        /// Move the component at the first index after the one at the second
+        Move {
+            component: usize,
+            predecessor: Option<usize>,
+        },
+        Rebalance,
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            4 => any::<Option<usize>>().prop_map(|predecessor| Operation::Insert { predecessor }),
+            4 => (any::<usize>(), any::<Option<usize>>())
+                .prop_map(|(component, predecessor)| Operation::Move { component, predecessor }),
+            1 => Just(Operation::Rebalance),
+        ]
+    }
+
+    fn sorted(components: &mut [PromptComponent]) {
+        components.sort_by(|a, b| a.ordering.cmp(&b.ordering));
+    }
+
+    proptest! {
+        #[test]
+        fn operations_preserve_order(operations in prop::collection::vec(operation(), 1..200)) {
+            let mut components: Vec<PromptComponent> = Vec::new();
+            let mut expected: Vec<ComponentID> = Vec::new();
+
+            for (id, operation) in operations.into_iter().enumerate() {
+                let pick = |index: usize| expected[index % expected.len()];
+
+                match operation {
+                    Operation::Insert { predecessor } => {
+                        let predecessor = predecessor.filter(|_| !expected.is_empty()).map(pick);
+                        let ordering = PromptComponent::sort_key_after(&components, predecessor)
+                            .expect("Predecessor exists");
+
+                        let component_id = ComponentID(id as u64);
+                        let position = predecessor
+                            .map_or(0, |predecessor| expected.iter().position(|id| *id == predecessor).unwrap() + 1);
+                        expected.insert(position, component_id);
+                        components.push(PromptComponent {
+                            component_id,
+                            template_id: TemplateID::default(),
+                            ordering,
+                            text: String::new(),
+                            deleted_at: None,
+                        });
+                    }
+                    Operation::Move { component, predecessor } => {
+                        if expected.is_empty() {
+                            continue;
+                        }
+                        let component_id = pick(component);
+                        let predecessor = predecessor.map(pick);
+                        if predecessor == Some(component_id) {
+                            continue;
+                        }
+
+                        let ordering = PromptComponent::sort_key_after(&components, predecessor)
+                            .expect("Predecessor exists");
+
+                        expected.retain(|id| *id != component_id);
+                        let position = predecessor
+                            .map_or(0, |predecessor| expected.iter().position(|id| *id == predecessor).unwrap() + 1);
+                        expected.insert(position, component_id);
+                        components
+                            .iter_mut()
+                            .find(|component| component.component_id == component_id)
+                            .unwrap()
+                            .ordering = ordering;
+                    }
+                    Operation::Rebalance => {
+                        let keys = PromptComponent::create_balanced_sort_keys(components.len());
+                        for (component, ordering) in components.iter_mut().zip(keys) {
+                            component.ordering = ordering;
+                        }
+                    }
+                }
+
+                sorted(&mut components);
+                let order: Vec<_> = components.iter().map(|component| component.component_id).collect();
+                prop_assert_eq!(&order, &expected);
+                prop_assert!(components.windows(2).all(|pair| pair[0].ordering < pair[1].ordering));
+                prop_assert!(components.iter().all(|component| !component.ordering.ends_with('0')));
+            }
+        }
+
+        #[test]
+        fn keys_fit_between_legacy_keys(zeros in 0..20usize, extra in 1..20usize) {
+            // Keys of the previous scheme consist of zeros followed by a single one
+            let predecessor = format!("{}1", "0".repeat(zeros + extra));
+            let successor = format!("{}1", "0".repeat(zeros));
+
+            let key = PromptComponent::create_sort_key_between(Some(&predecessor), Some(&successor));
+            prop_assert!(predecessor < key && key < successor);
+            let first = PromptComponent::create_sort_key_between(None, Some(&predecessor));
+            prop_assert!(first < predecessor);
+            let last = PromptComponent::create_sort_key_between(Some(&successor), None);
+            prop_assert!(successor < last);
+        }
+
+        #[test]
+        fn balanced_keys_are_short_and_sorted(count in 0..10_000usize) {
+            let keys = PromptComponent::create_balanced_sort_keys(count);
+            prop_assert_eq!(keys.len(), count);
+            prop_assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            prop_assert!(keys.iter().all(|key| key.len() <= 3 && !key.ends_with('0')));
+        }
     }
 }
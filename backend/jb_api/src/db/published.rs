@@ -0,0 +1,30 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Level, LevelID};
+
+/// Immutable snapshot of a level as seen by players
+///
+/// Admins edit the draft in [`Level`] and its prompt components, publishing copies both
+/// into a new version. Only the latest version of each level is live.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PublishedLevel {
+    pub level_id: LevelID,
+    pub version: u64,
+    pub published_at: u64,
+    pub published_by: String,
+    /// The level's draft at the time of publishing
+    pub level: Level,
+    /// Texts of the level's prompt components in prompt order
+    pub prompt: Vec<String>,
+    /// Texts of all components of the level's template by id, for includes
+    #[serde(default)]
+    pub library: BTreeMap<String, String>,
+}
+
+impl PublishedLevel {
+    pub const TABLE: &'static str = "jb_published_levels";
+    pub const PARTITION: &'static str = "level_id";
+    pub const SORT: &'static str = "version";
+}
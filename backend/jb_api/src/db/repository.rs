@@ -0,0 +1,182 @@
+use async_trait::async_trait;
+use axum::BoxError;
+
+use super::{
+    ChatSession, ChatSessionPage, ChatTurn, ComponentID, ComponentRevision, Level, LevelID,
+    LevelSolve, LevelUpdate, NewChatTurn, NewComponentRevision, PromptComponent, PromptTemplate,
+    PublishedLevel, TemplateID,
+};
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// The addressed item does not exist
+    NotFound,
+    /// The storage backend failed to process the request
+    Backend(BoxError),
+}
+
+impl std::fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepositoryError::NotFound => write!(f, "Item does not exist"),
+            RepositoryError::Backend(err) => write!(f, "Storage backend error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RepositoryError::NotFound => None,
+            RepositoryError::Backend(err) => {
+                let source: &(dyn std::error::Error + 'static) = &**err;
+                Some(source)
+            }
+        }
+    }
+}
+
+pub type RepositoryResult<T> = Result<T, RepositoryError>;
+
+/// Most items a single atomic repository operation can change, the limit of DynamoDB transactions
+pub const MAX_TRANSACTION_ITEMS: usize = 100;
+
+/// Refuse operations which would not fit into a single transaction
+pub(super) fn check_transaction_size(items: usize) -> RepositoryResult<()> {
+    if items > MAX_TRANSACTION_ITEMS {
+        return Err(RepositoryError::Backend(
+            format!("Cannot change more than {MAX_TRANSACTION_ITEMS} items at once").into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Storage backend for all persistent data of the API
+///
+/// Route handlers only talk to the database through this trait,
+/// so they can be run against [`super::DynamoRepository`] in production
+/// and against [`super::MemoryRepository`] in tests.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    /// Atomically increment the named counter and return its new value
+    async fn increment_counter(&self, counter: &'static str) -> RepositoryResult<u64>;
+
+    async fn get_levels(&self) -> RepositoryResult<Vec<Level>>;
+    async fn get_level(&self, level_id: LevelID) -> RepositoryResult<Level>;
+    async fn put_level(&self, level: &Level) -> RepositoryResult<()>;
+    /// Fails with [`RepositoryError::NotFound`] if the level does not exist
+    async fn update_level(&self, level_id: LevelID, update: LevelUpdate) -> RepositoryResult<()>;
+    async fn delete_level(&self, level_id: LevelID) -> RepositoryResult<()>;
+    /// Atomically move a level to the trash and apply updates removing references to it
+    /// from other levels
+    ///
+    /// Fails with [`RepositoryError::NotFound`] without changing anything
+    /// if any of the levels does not exist.
+    /// At most [`MAX_TRANSACTION_ITEMS`] minus one references can be removed at once.
+    async fn trash_level(
+        &self,
+        level_id: LevelID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()>;
+
+    /// Store a new version of a level
+    ///
+    /// Returns `false` without changing anything if the version was already published.
+    async fn publish_level(&self, snapshot: &PublishedLevel) -> RepositoryResult<bool>;
+    /// Latest published version of every level
+    async fn get_published_levels(&self) -> RepositoryResult<Vec<PublishedLevel>>;
+    /// Latest published version of a level
+    async fn get_published_level(&self, level_id: LevelID) -> RepositoryResult<PublishedLevel>;
+    /// Remove all published versions of a level
+    async fn delete_published_level(&self, level_id: LevelID) -> RepositoryResult<()>;
+
+    /// All stored templates, in no particular order
+    async fn get_templates(&self) -> RepositoryResult<Vec<PromptTemplate>>;
+    async fn get_template(&self, template_id: &TemplateID) -> RepositoryResult<PromptTemplate>;
+    async fn put_template(&self, template: &PromptTemplate) -> RepositoryResult<()>;
+    /// Fails with [`RepositoryError::NotFound`] if the template does not exist
+    async fn update_template_name(
+        &self,
+        template_id: &TemplateID,
+        name: String,
+    ) -> RepositoryResult<()>;
+    async fn delete_template(&self, template_id: &TemplateID) -> RepositoryResult<()>;
+
+    /// All components of a template, sorted by their ordering key
+    async fn get_components(
+        &self,
+        template_id: &TemplateID,
+    ) -> RepositoryResult<Vec<PromptComponent>>;
+    async fn get_component(&self, component_id: ComponentID) -> RepositoryResult<PromptComponent>;
+    async fn put_component(&self, component: &PromptComponent) -> RepositoryResult<()>;
+    /// Atomically replace the component's text and append the edit to its history
+    ///
+    /// If the component has no history yet, its previous text is kept as the first revision.
+    /// Fails with [`RepositoryError::NotFound`] if the component does not exist.
+    async fn revise_component_text(
+        &self,
+        revision: NewComponentRevision,
+    ) -> RepositoryResult<ComponentRevision>;
+    /// All revisions of a component, oldest first
+    async fn get_component_revisions(
+        &self,
+        component_id: ComponentID,
+    ) -> RepositoryResult<Vec<ComponentRevision>>;
+    /// Remove the whole history of a component
+    async fn delete_component_revisions(&self, component_id: ComponentID) -> RepositoryResult<()>;
+    /// Fails with [`RepositoryError::NotFound`] if the component does not exist
+    async fn update_component_ordering(
+        &self,
+        component_id: ComponentID,
+        ordering: String,
+    ) -> RepositoryResult<()>;
+    /// Atomically replace the ordering keys of several components of a template
+    ///
+    /// Fails with [`RepositoryError::NotFound`] without changing anything
+    /// if any of the components does not exist or belongs to another template.
+    /// At most [`MAX_TRANSACTION_ITEMS`] components can be updated at once.
+    async fn update_component_orderings(
+        &self,
+        template_id: &TemplateID,
+        orderings: Vec<(ComponentID, String)>,
+    ) -> RepositoryResult<()>;
+    async fn delete_component(&self, component_id: ComponentID) -> RepositoryResult<()>;
+    /// Atomically move a component to the trash and apply updates removing it
+    /// from the levels using it
+    ///
+    /// Fails with [`RepositoryError::NotFound`] without changing anything
+    /// if the component or any of the levels does not exist.
+    /// At most [`MAX_TRANSACTION_ITEMS`] minus one references can be removed at once.
+    async fn trash_component(
+        &self,
+        component_id: ComponentID,
+        deleted_at: u64,
+        references: Vec<(LevelID, LevelUpdate)>,
+    ) -> RepositoryResult<()>;
+    /// Fails with [`RepositoryError::NotFound`] if the component does not exist
+    async fn restore_component(&self, component_id: ComponentID) -> RepositoryResult<()>;
+    /// Components of all templates, in no particular order
+    async fn get_all_components(&self) -> RepositoryResult<Vec<PromptComponent>>;
+
+    /// Append a turn to the session's transcript, creating the session if necessary
+    async fn record_chat_turn(&self, turn: NewChatTurn) -> RepositoryResult<()>;
+    /// All sessions of a player, most recently active first
+    async fn get_chat_sessions(&self, username: &str) -> RepositoryResult<Vec<ChatSession>>;
+    async fn get_chat_session(&self, session_id: &str) -> RepositoryResult<ChatSession>;
+    /// Up to `limit` sessions of all players, continuing after the page which `after` ended
+    ///
+    /// Pages are in no particular order.
+    async fn scan_chat_sessions(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> RepositoryResult<ChatSessionPage>;
+    /// All turns of a session in chronological order
+    async fn get_chat_turns(&self, session_id: &str) -> RepositoryResult<Vec<ChatTurn>>;
+
+    /// Record a solve, keeping the earlier one if the player already solved the level
+    async fn record_level_solve(&self, solve: LevelSolve) -> RepositoryResult<()>;
+    /// Solves of a player, ordered by level
+    async fn get_level_solves(&self, username: &str) -> RepositoryResult<Vec<LevelSolve>>;
+}
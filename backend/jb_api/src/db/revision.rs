@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use super::ComponentID;
+
+/// Text of a prompt component as saved by a single edit
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ComponentRevision {
+    pub component_id: ComponentID,
+    /// Position in the component's history, starting at 0
+    pub revision: u64,
+    /// Moderator who saved the text, unknown for text predating the history
+    pub author: Option<String>,
+    pub timestamp: u64,
+    pub text: String,
+    /// Revision whose text was restored by a rollback
+    #[serde(default)]
+    pub restored_from: Option<u64>,
+}
+
+impl ComponentRevision {
+    pub const TABLE: &'static str = "jb_component_revisions";
+    pub const PARTITION: &'static str = "component_id";
+    pub const SORT: &'static str = "revision";
+}
+
+/// Edit to append to a component's history, the revision number is assigned by the repository
+#[derive(Debug)]
+pub struct NewComponentRevision {
+    pub component_id: ComponentID,
+    pub author: String,
+    pub timestamp: u64,
+    pub text: String,
+    pub restored_from: Option<u64>,
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use super::LevelID;
+
+/// First successful password validation of a player on a level
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LevelSolve {
+    pub username: String,
+    pub level_id: LevelID,
+    pub solved_at: u64,
+    /// Messages the player sent on the level up to the solve
+    #[serde(default)]
+    pub message_count: u64,
+}
+
+impl LevelSolve {
+    pub const TABLE: &'static str = "jb_level_solves";
+    pub const PARTITION: &'static str = "username";
+    pub const SORT: &'static str = "level_id";
+}
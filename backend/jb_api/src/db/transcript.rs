@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::LevelID;
+
+/// A conversation of a player with the model of a level
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatSession {
+    /// SHA-256 of the username, the level and the client's session id
+    pub session_id: String,
+    /// Session id chosen by the client, needed to continue the conversation
+    pub client_session_id: String,
+    pub level_id: LevelID,
+    pub username: String,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub turn_count: u64,
+    /// Time of the first reply which leaked the level password
+    #[serde(default)]
+    pub first_leak_at: Option<u64>,
+    /// Number of replies which leaked the level password
+    #[serde(default)]
+    pub leak_count: u64,
+}
+
+impl ChatSession {
+    pub const TABLE: &'static str = "jb_chat_sessions";
+    pub const PARTITION: &'static str = "session_id";
+
+    pub const SECONDARY_USERNAME_INDEX: &'static str = "username-index";
+    pub const SECONDARY_USERNAME: &'static str = "username";
+    pub const SECONDARY_USERNAME_UPDATED_AT: &'static str = "updated_at";
+
+    pub const CLIENT_SESSION_ID: &'static str = "client_session_id";
+    pub const LEVEL_ID: &'static str = "level_id";
+    pub const STARTED_AT: &'static str = "started_at";
+    pub const TURN_COUNT: &'static str = "turn_count";
+    pub const FIRST_LEAK_AT: &'static str = "first_leak_at";
+    pub const LEAK_COUNT: &'static str = "leak_count";
+
+    /// Session ids are chosen by clients, so they are namespaced by the username and the level
+    ///
+    /// The id is a stored key, so it must stay the same across releases and toolchains.
+    pub fn id_for(username: &str, level_id: LevelID, client_session_id: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((username.len() as u64).to_be_bytes());
+        hasher.update(username);
+        hasher.update(level_id.0.to_be_bytes());
+        hasher.update(client_session_id);
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Username sessions of a guest are stored under
+    ///
+    /// Guests only claim a username, so their sessions must not mix with a verified player's.
+    pub fn guest_username(claimed: &str) -> String {
+        format!("guest:{claimed}")
+    }
+}
+
+/// Sessions read by one scan of all sessions
+#[derive(Debug)]
+pub struct ChatSessionPage {
+    pub sessions: Vec<ChatSession>,
+    /// Session to continue the scan after, `None` once all sessions were read
+    pub next: Option<String>,
+}
+
+/// How the level password showed up in a model reply
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PasswordLeak {
+    /// The password as is
+    Verbatim,
+    /// The password with spaces or punctuation between its characters
+    Spaced,
+    /// The password spelled out letter by letter, possibly with the NATO alphabet
+    Spelled,
+    Reversed,
+    Rot13,
+    Base64,
+}
+
+/// A player message together with the model's reply
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatTurn {
+    pub session_id: String,
+    /// Position of the turn in the session, starting at 0
+    pub turn: u64,
+    pub message: String,
+    pub reply: String,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub leak: Option<PasswordLeak>,
+}
+
+impl ChatTurn {
+    pub const TABLE: &'static str = "jb_chat_turns";
+    pub const PARTITION: &'static str = "session_id";
+    pub const SORT: &'static str = "turn";
+}
+
+/// Turn to append to a transcript, the session is created with its first turn
+#[derive(Debug)]
+pub struct NewChatTurn {
+    pub session_id: String,
+    pub client_session_id: String,
+    pub level_id: LevelID,
+    pub username: String,
+    pub message: String,
+    pub reply: String,
+    pub timestamp: u64,
+    pub leak: Option<PasswordLeak>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ids_are_stable() {
+        assert_eq!(
+            ChatSession::id_for("player", LevelID(1), "session-1"),
+            "1149a9a2cefddff1d61c62f68656c4d9bc0852df8c3ea9d3aa42dea2d143d51e"
+        );
+    }
+
+    #[test]
+    fn session_ids_separate_username_and_client_id() {
+        assert_ne!(
+            ChatSession::id_for("player", LevelID(1), "1-session"),
+            ChatSession::id_for("player1", LevelID(1), "-session")
+        );
+    }
+
+    #[test]
+    fn session_ids_separate_levels_and_guests() {
+        let id = ChatSession::id_for("player", LevelID(1), "session");
+        assert_ne!(id, ChatSession::id_for("player", LevelID(2), "session"));
+        assert_ne!(
+            id,
+            ChatSession::id_for(
+                &ChatSession::guest_username("player"),
+                LevelID(1),
+                "session"
+            )
+        );
+    }
+}
@@ -1,13 +1,16 @@
+#![recursion_limit = "256"]
+
 use std::sync::Arc;
 
-use aws_config::SdkConfig;
 use lambda_http::tower::Layer;
-use tower_http::normalize_path::NormalizePathLayer;
+use tower_http::normalize_path::{NormalizePath, NormalizePathLayer};
 
 #[macro_use]
 mod macros;
-mod auth;
-mod db;
+pub mod auth;
+pub mod db;
+pub mod llm;
+mod render;
 mod response;
 mod routes;
 
@@ -16,11 +19,32 @@ pub use routes::generate_terraform;
 pub type ExtractState = axum::extract::State<State>;
 pub type State = Arc<InnerState>;
 pub struct InnerState {
-    #[allow(dead_code)]
-    sdk_config: SdkConfig,
-    bedrockagent: aws_sdk_bedrockagentruntime::Client,
-    dynamo: aws_sdk_dynamodb::Client,
-    cognito: aws_sdk_cognitoidentityprovider::Client,
+    groups: Box<dyn auth::UserGroups>,
+    repository: Box<dyn db::Repository>,
+    models: llm::ChatModels,
+}
+
+impl InnerState {
+    pub fn new(
+        groups: impl auth::UserGroups + 'static,
+        repository: impl db::Repository + 'static,
+        models: llm::ChatModels,
+    ) -> Self {
+        Self {
+            groups: Box::new(groups),
+            repository: Box::new(repository),
+            models,
+        }
+    }
+}
+
+/// Build the complete API service around the given state
+pub fn create_app(state: InnerState) -> NormalizePath<axum::Router> {
+    let router = routes::create_router()
+        .layer(tower_http::trace::TraceLayer::new_for_http())
+        .with_state(Arc::new(state));
+
+    NormalizePathLayer::append_trailing_slash().layer(router)
 }
 
 pub async fn run() {
@@ -35,7 +59,7 @@ pub async fn run() {
     };
 
     let sdk_config = aws_config::from_env().http_client(http_client).load().await;
-    
+
     #[cfg(feature = "local-testing")]
     {
         use aws_sdk_dynamodb::config::ProvideCredentials;
@@ -52,22 +76,10 @@ pub async fn run() {
         }
     }
 
-    let bedrockagent = aws_sdk_bedrockagentruntime::Client::new(&sdk_config);
-    let dynamo = aws_sdk_dynamodb::Client::new(&sdk_config);
     let cognito = aws_sdk_cognitoidentityprovider::Client::new(&sdk_config);
+    let dynamo = aws_sdk_dynamodb::Client::new(&sdk_config);
+    let models = llm::ChatModels::from_env(&sdk_config);
+    let inner_state = InnerState::new(cognito, db::DynamoRepository::new(dynamo), models);
 
-    let inner_state = InnerState {
-        sdk_config,
-        bedrockagent,
-        dynamo,
-        cognito,
-    };
-
-    let router = routes::create_router()
-        .layer(tower_http::trace::TraceLayer::new_for_http())
-        .with_state(Arc::new(inner_state));
-
-    let router = NormalizePathLayer::append_trailing_slash().layer(router);
-
-    lambda_http::run(router).await.unwrap();
+    lambda_http::run(create_app(inner_state)).await.unwrap();
 }
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use aws_sdk_bedrockagentruntime::types::{
+    CreationMode, InferenceConfiguration, InlineAgentPayloadPart, InlineAgentResponseStream,
+    PromptConfiguration, PromptOverrideConfiguration, PromptState, PromptType,
+};
+use futures::stream;
+use indoc::indoc;
+use serde_json::json;
+
+use super::*;
+
+/// Bedrock rejects inline agents with shorter instructions
+pub const MIN_INSTRUCTION_LENGTH: usize = 40;
+
+/// Pad the instruction with spaces up to [`MIN_INSTRUCTION_LENGTH`]
+pub fn pad_instruction(instruction: &str) -> String {
+    let mut padded = instruction.to_owned();
+    if padded.len() < MIN_INSTRUCTION_LENGTH {
+        padded.push_str(&" ".repeat(MIN_INSTRUCTION_LENGTH - padded.len()));
+    }
+    padded
+}
+
+/// Longest reply an inline agent accepts, in tokens
+pub const MAXIMUM_LENGTH: u32 = 4096;
+
+/// Orchestration prompt template, Bedrock substitutes `$instruction$` and `$question$`
+pub fn base_prompt() -> serde_json::Value {
+    json!({
+        "system": indoc!("
+            ALWAYS provide your final response to the User request within <answer> </answer> tags! Do not use it to ask questions!
+            Additionally, follow these instructions:
+            $instruction$
+        "),
+        "messages": [
+            {
+                "role" : "user",
+                "content": [{
+                    "text": "$question$"
+                }]
+            },
+            //{
+            //    "role" : "assistant",
+            //    "content" : [{
+            //        "text": "Response: <answer>"
+            //    }]
+            //}
+        ]
+    })
+}
+
+/// Bedrock inline agent with all steps except orchestration disabled
+pub struct BedrockChatModel {
+    client: aws_sdk_bedrockagentruntime::Client,
+}
+
+impl BedrockChatModel {
+    pub fn new(client: aws_sdk_bedrockagentruntime::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ChatModel for BedrockChatModel {
+    fn maximum_length(&self) -> u32 {
+        MAXIMUM_LENGTH
+    }
+
+    async fn stream(
+        &self,
+        model_id: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<ReplyStream, ChatModelError> {
+        let instruction = pad_instruction(request.instruction);
+        let maximum_length = i32::try_from(request.maximum_length)
+            .map_err(|e| ChatModelError::InvocationFailed(Box::new(e)))?;
+
+        let response = self
+            .client
+            .invoke_inline_agent()
+            .session_id(request.session_id)
+            .idle_session_ttl_in_seconds(SESSION_IDLE_TTL.as_secs() as i32)
+            .foundation_model(model_id)
+            .prompt_override_configuration(
+                PromptOverrideConfiguration::builder()
+                    .prompt_configurations(
+                        PromptConfiguration::builder()
+                            .prompt_type(PromptType::PreProcessing)
+                            .prompt_state(PromptState::Disabled)
+                            .build(),
+                    )
+                    .prompt_configurations(
+                        PromptConfiguration::builder()
+                            .prompt_type(PromptType::KnowledgeBaseResponseGeneration)
+                            .prompt_state(PromptState::Disabled)
+                            .build(),
+                    )
+                    .prompt_configurations(
+                        PromptConfiguration::builder()
+                            .prompt_type(PromptType::PostProcessing)
+                            .prompt_state(PromptState::Disabled)
+                            .build(),
+                    )
+                    .prompt_configurations(
+                        PromptConfiguration::builder()
+                            .prompt_type(PromptType::Orchestration)
+                            .prompt_state(PromptState::Enabled)
+                            .parser_mode(CreationMode::Default)
+                            .inference_configuration(
+                                InferenceConfiguration::builder()
+                                    .maximum_length(maximum_length)
+                                    .temperature(request.temperature)
+                                    .set_stop_sequences(Some(request.stop_sequences.to_vec()))
+                                    .build(),
+                            )
+                            .prompt_creation_mode(CreationMode::Overridden)
+                            .base_prompt_template(base_prompt().to_string())
+                            .build(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .instruction(instruction)
+            .input_text(request.message)
+            .send()
+            .await
+            .map_err(|e| ChatModelError::InvocationFailed(Box::new(e)))?;
+
+        let reply = stream::try_unfold(
+            (response.completion, Vec::new()),
+            |(mut completion, mut pending)| async move {
+                loop {
+                    let Some(event) = completion
+                        .recv()
+                        .await
+                        .map_err(|e| ChatModelError::InvocationFailed(Box::new(e)))?
+                    else {
+                        if !pending.is_empty() {
+                            return Err(ChatModelError::IllegalModelResponse);
+                        }
+                        return Ok(None);
+                    };
+
+                    if let InlineAgentResponseStream::Chunk(InlineAgentPayloadPart {
+                        bytes: Some(chunk),
+                        ..
+                    }) = event
+                    {
+                        pending.extend(chunk.as_ref());
+                        let text = take_utf8(&mut pending)?;
+                        if !text.is_empty() {
+                            return Ok(Some((text, (completion, pending))));
+                        }
+                    }
+                }
+            },
+        );
+
+        Ok(reply.boxed())
+    }
+}
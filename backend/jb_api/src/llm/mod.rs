@@ -0,0 +1,200 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use axum::BoxError;
+use futures::{StreamExt, TryStreamExt, stream::BoxStream};
+
+mod bedrock;
+mod openai;
+mod scripted;
+
+pub use bedrock::{BedrockChatModel, MIN_INSTRUCTION_LENGTH, base_prompt, pad_instruction};
+pub use openai::OpenAiChatModel;
+pub use scripted::{ScriptedChatModel, ScriptedRule};
+
+/// Model used when no other model is configured through `DEFAULT_CHAT_MODEL`
+pub const DEFAULT_MODEL: &str = "bedrock:eu.meta.llama3-2-3b-instruct-v1:0";
+
+/// Time after which an inactive conversation is forgotten by the model backend
+pub const SESSION_IDLE_TTL: Duration = Duration::from_secs(120);
+
+/// A single turn of a conversation, as handed to a [`ChatModel`]
+#[derive(Debug)]
+pub struct CompletionRequest<'a> {
+    /// Identifies the conversation, previous turns of the same session are remembered by the backend
+    pub session_id: &'a str,
+    /// System prompt assembled from the level's prompt components
+    pub instruction: &'a str,
+    pub message: &'a str,
+    pub temperature: f32,
+    pub maximum_length: u32,
+    pub stop_sequences: &'a [String],
+}
+
+error_response!(ChatModelError {
+    /// Model provider {provider} is not configured
+    UnknownProvider { provider: String },
+    /// Failed to invoke model
+    InvocationFailed(BoxError),
+    /// Model responded with Non-UTF8 characters
+    IllegalModelResponse,
+});
+
+/// Parts of a reply in the order they were produced by the model
+pub type ReplyStream = BoxStream<'static, Result<String, ChatModelError>>;
+
+#[async_trait]
+pub trait ChatModel: Send + Sync {
+    /// Longest reply the provider accepts, in tokens
+    fn maximum_length(&self) -> u32 {
+        u32::MAX
+    }
+
+    /// Start producing the model's reply to the request's message
+    async fn stream(
+        &self,
+        model_id: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<ReplyStream, ChatModelError>;
+
+    /// Produce the model's complete reply to the request's message
+    async fn complete(
+        &self,
+        model_id: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<String, ChatModelError> {
+        self.stream(model_id, request)
+            .await?
+            .try_fold(String::new(), |mut reply, chunk| async move {
+                reply.push_str(&chunk);
+                Ok(reply)
+            })
+            .await
+    }
+}
+
+/// Split off the longest valid UTF-8 prefix of `bytes`,
+/// leaving a character that is cut off at the end in place
+fn take_utf8(bytes: &mut Vec<u8>) -> Result<String, ChatModelError> {
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => return Err(ChatModelError::IllegalModelResponse),
+    };
+
+    let rest = bytes.split_off(valid);
+    let text = std::mem::replace(bytes, rest);
+    Ok(String::from_utf8(text).expect("Prefix was validated as UTF-8"))
+}
+
+/// Split a model given as `provider:model_id` into its parts
+pub fn split_model(model: &str) -> (&str, &str) {
+    model.split_once(':').unwrap_or((model, ""))
+}
+
+/// All configured model providers
+///
+/// Models are addressed as `provider:model_id`, e.g. `bedrock:eu.meta.llama3-2-3b-instruct-v1:0`
+/// or `openai:llama3.2`, so switching the model of a level does not require a redeployment.
+pub struct ChatModels {
+    providers: HashMap<String, Box<dyn ChatModel>>,
+    default_model: String,
+}
+
+impl ChatModels {
+    pub fn new(default_model: impl Into<String>) -> Self {
+        Self {
+            providers: HashMap::new(),
+            default_model: default_model.into(),
+        }
+    }
+
+    pub fn with_provider(
+        mut self,
+        provider: impl Into<String>,
+        model: impl ChatModel + 'static,
+    ) -> Self {
+        self.providers.insert(provider.into(), Box::new(model));
+        self
+    }
+
+    /// Register Bedrock and, if `OPENAI_BASE_URL` is set, an OpenAI compatible endpoint
+    ///
+    /// `SCRIPTED_MODEL_RULES` may point to a rule file for a [`ScriptedChatModel`],
+    /// which is then available as the `scripted` provider.
+    pub fn from_env(sdk_config: &SdkConfig) -> Self {
+        let default_model =
+            std::env::var("DEFAULT_CHAT_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_owned());
+
+        let mut models = ChatModels::new(default_model).with_provider(
+            "bedrock",
+            BedrockChatModel::new(aws_sdk_bedrockagentruntime::Client::new(sdk_config)),
+        );
+
+        if let Ok(base_url) = std::env::var("OPENAI_BASE_URL") {
+            models = models.with_provider(
+                "openai",
+                OpenAiChatModel::new(base_url, std::env::var("OPENAI_API_KEY").ok()),
+            );
+        }
+
+        if let Ok(path) = std::env::var("SCRIPTED_MODEL_RULES") {
+            models = models.with_provider(
+                "scripted",
+                ScriptedChatModel::from_file(&path).expect("Unable to load scripted model rules"),
+            );
+        }
+
+        models
+    }
+
+    pub fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    /// Whether the provider of `model`, given as `provider:model_id`, is configured
+    pub fn is_available(&self, model: &str) -> bool {
+        let (provider, _) = split_model(model);
+        self.providers.contains_key(provider)
+    }
+
+    /// Longest reply `model`, given as `provider:model_id`, accepts, in tokens
+    pub fn maximum_length(&self, model: &str) -> Result<u32, ChatModelError> {
+        let (backend, _) = self.resolve(model)?;
+        Ok(backend.maximum_length())
+    }
+
+    fn resolve<'a>(&self, model: &'a str) -> Result<(&dyn ChatModel, &'a str), ChatModelError> {
+        let (provider, model_id) = split_model(model);
+
+        let backend =
+            self.providers
+                .get(provider)
+                .ok_or_else(|| ChatModelError::UnknownProvider {
+                    provider: provider.to_owned(),
+                })?;
+
+        Ok((backend.as_ref(), model_id))
+    }
+
+    /// Run the request against `model`, given as `provider:model_id`
+    pub async fn complete(
+        &self,
+        model: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<String, ChatModelError> {
+        let (backend, model_id) = self.resolve(model)?;
+        backend.complete(model_id, request).await
+    }
+
+    /// Run the request against `model`, returning the reply as it is generated
+    pub async fn stream(
+        &self,
+        model: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<ReplyStream, ChatModelError> {
+        let (backend, model_id) = self.resolve(model)?;
+        backend.stream(model_id, request).await
+    }
+}
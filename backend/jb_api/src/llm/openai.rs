@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use async_trait::async_trait;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::response::MapBoxError;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+struct Session {
+    history: Vec<Message>,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+struct Sessions(Mutex<HashMap<String, Session>>);
+
+impl Sessions {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn history(&self, session_id: &str) -> Vec<Message> {
+        let mut sessions = self.lock();
+        sessions.retain(|_, session| session.last_used.elapsed() < SESSION_IDLE_TTL);
+        sessions
+            .get(session_id)
+            .map(|session| session.history.clone())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, session_id: String, user_message: Message, reply: String) {
+        let mut sessions = self.lock();
+        let session = sessions.entry(session_id).or_insert_with(|| Session {
+            history: Vec::new(),
+            last_used: Instant::now(),
+        });
+        session.history.push(user_message);
+        session.history.push(Message {
+            role: "assistant".to_owned(),
+            content: reply,
+        });
+        session.last_used = Instant::now();
+    }
+}
+
+/// Turn whose reply is still being streamed, added to the history once complete
+struct PendingTurn {
+    sessions: Arc<Sessions>,
+    session_id: String,
+    user_message: Message,
+    reply: String,
+}
+
+impl PendingTurn {
+    fn finish(self) {
+        self.sessions
+            .record(self.session_id, self.user_message, self.reply);
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<Message>,
+    temperature: f32,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<&'a [String]>,
+    stream: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionChunk {
+    choices: Vec<ChatCompletionChunkChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionChunkChoice {
+    delta: ChatCompletionDelta,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionDelta {
+    content: Option<String>,
+}
+
+/// Any server implementing the OpenAI `/chat/completions` API, e.g. llama.cpp or Ollama
+///
+/// These servers are stateless, so the conversation history is kept in memory
+/// and forgotten after [`SESSION_IDLE_TTL`], mirroring Bedrock's session handling.
+pub struct OpenAiChatModel {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    sessions: Arc<Sessions>,
+}
+
+impl OpenAiChatModel {
+    pub fn new(base_url: impl Into<String>, api_key: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            api_key,
+            sessions: Arc::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl ChatModel for OpenAiChatModel {
+    async fn stream(
+        &self,
+        model_id: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<ReplyStream, ChatModelError> {
+        let user_message = Message {
+            role: "user".to_owned(),
+            content: request.message.to_owned(),
+        };
+
+        let mut messages = vec![Message {
+            role: "system".to_owned(),
+            content: request.instruction.to_owned(),
+        }];
+        messages.extend(self.sessions.history(request.session_id));
+        messages.push(user_message.clone());
+
+        let mut http_request = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&ChatCompletionRequest {
+                model: model_id,
+                messages,
+                temperature: request.temperature,
+                max_tokens: request.maximum_length,
+                stop: (!request.stop_sequences.is_empty()).then_some(request.stop_sequences),
+                stream: true,
+            });
+
+        if let Some(api_key) = &self.api_key {
+            http_request = http_request.bearer_auth(api_key);
+        }
+
+        let response = http_request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .box_error()
+            .map_err(ChatModelError::InvocationFailed)?;
+
+        let turn = PendingTurn {
+            sessions: self.sessions.clone(),
+            session_id: request.session_id.to_owned(),
+            user_message,
+            reply: String::new(),
+        };
+
+        // The body consists of server-sent events, each carrying a chunk of the reply
+        let reply = stream::try_unfold(
+            (response.bytes_stream().boxed(), Vec::new(), Some(turn)),
+            |(mut body, mut buffer, mut turn)| async move {
+                loop {
+                    let Some(newline) = buffer.iter().position(|&byte| byte == b'\n') else {
+                        match body.next().await {
+                            Some(bytes) => buffer.extend_from_slice(
+                                &bytes
+                                    .box_error()
+                                    .map_err(ChatModelError::InvocationFailed)?,
+                            ),
+                            // Servers may close the stream without sending `[DONE]`
+                            None => {
+                                if let Some(turn) = turn.take() {
+                                    turn.finish();
+                                }
+                                return Ok(None);
+                            }
+                        }
+                        continue;
+                    };
+
+                    let line: Vec<u8> = buffer.drain(..=newline).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    let Some(data) = line.trim().strip_prefix("data:").map(str::trim_start) else {
+                        continue;
+                    };
+
+                    if data == "[DONE]" {
+                        if let Some(turn) = turn.take() {
+                            turn.finish();
+                        }
+                        return Ok(None);
+                    }
+
+                    let chunk: ChatCompletionChunk = serde_json::from_str(data)
+                        .box_error()
+                        .map_err(ChatModelError::InvocationFailed)?;
+
+                    let text: String = chunk
+                        .choices
+                        .into_iter()
+                        .filter_map(|choice| choice.delta.content)
+                        .collect();
+
+                    if !text.is_empty() {
+                        if let Some(turn) = &mut turn {
+                            turn.reply.push_str(&text);
+                        }
+                        return Ok(Some((text, (body, buffer, turn))));
+                    }
+                }
+            },
+        );
+
+        Ok(reply.boxed())
+    }
+}
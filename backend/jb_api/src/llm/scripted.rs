@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use futures::stream;
+use serde::Deserialize;
+
+use super::*;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ScriptedRule {
+    /// Text the player message has to contain, ignoring case
+    pub contains: String,
+    /// Reply to send, `{{INSTRUCTION}}` and `{{MESSAGE}}` are replaced with the request's values
+    pub reply: String,
+}
+
+/// Deterministic stand-in for a real model, answering from a fixed rule table
+///
+/// The first rule matching the player message is used, the fallback reply otherwise.
+/// Since models never see the level password directly, tests wanting a leak
+/// should use a level whose prompt contains `{{LEVEL_PASSWORD}}` and reply with `{{INSTRUCTION}}`.
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct ScriptedChatModel {
+    #[serde(default)]
+    rules: Vec<ScriptedRule>,
+    #[serde(default)]
+    fallback: String,
+}
+
+impl ScriptedChatModel {
+    pub fn new(fallback: impl Into<String>) -> Self {
+        Self {
+            rules: Vec::new(),
+            fallback: fallback.into(),
+        }
+    }
+
+    pub fn rule(mut self, contains: impl Into<String>, reply: impl Into<String>) -> Self {
+        self.rules.push(ScriptedRule {
+            contains: contains.into(),
+            reply: reply.into(),
+        });
+        self
+    }
+
+    /// Load the rule table from a JSON file of the form `{ "rules": [...], "fallback": "..." }`
+    pub fn from_file(path: &str) -> Result<Self, BoxError> {
+        let script = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&script)?)
+    }
+}
+
+#[async_trait]
+impl ChatModel for ScriptedChatModel {
+    async fn stream(
+        &self,
+        _model_id: &str,
+        request: CompletionRequest<'_>,
+    ) -> Result<ReplyStream, ChatModelError> {
+        let message = request.message.to_lowercase();
+
+        let reply = self
+            .rules
+            .iter()
+            .find(|rule| message.contains(&rule.contains.to_lowercase()))
+            .map_or(&self.fallback, |rule| &rule.reply);
+
+        let reply = reply
+            .replace("{{INSTRUCTION}}", request.instruction)
+            .replace("{{MESSAGE}}", request.message);
+
+        Ok(stream::once(async { Ok(reply) }).boxed())
+    }
+}
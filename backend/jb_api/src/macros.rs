@@ -8,9 +8,9 @@ macro_rules! api_routes {
             let api_spec = json!({ $(
                 [!set! #route = api_routes!(@route $( $route_stop ),*)]
                 #route: { $(
-                    [!set! #require_auth = api_routes!(@auth $auth)]
+                    [!set! #auth = api_routes!(@auth $auth)]
                     [!lower! $method]: {
-                        "require_auth": #require_auth
+                        "auth": #auth
                     }
                 ),* }
             ),* });
@@ -32,8 +32,9 @@ macro_rules! api_routes {
     (@route $( $route_stop:tt ),*) => { concat!("/", $( api_routes!(@route_stop $route_stop), "/" ),*) };
     (@route_stop $fixed:literal) => { $fixed };
     (@route_stop ($param_name:ident)) => { concat!("{", stringify!($param_name), "}") };
-    (@auth -) => { false };
-    (@auth |) => { true };
+    (@auth -) => { "none" };
+    (@auth |) => { "required" };
+    (@auth ?) => { "optional" };
 }
 
 macro_rules! error_response {
@@ -83,7 +84,7 @@ macro_rules! error_response {
                     ),*
                 }
             }
-            fn status_code(&self) -> ::axum::http::StatusCode { 
+            fn status_code(&self) -> ::axum::http::StatusCode {
                 match self {
                     $(
                         $enum_name::$variant
@@ -0,0 +1,136 @@
+//! Rendering of prompt component texts
+//!
+//! Component texts are [MiniJinja](https://docs.rs/minijinja) templates. Besides plain
+//! `{{ LEVEL_NAME }}` style variables, authors can use conditionals such as
+//! `{% if LEVEL_DIFFICULTY == "High" %}`, loops, and `{% include "<component id>" %}`
+//! to embed other components of the same template. Levels can define additional
+//! variables of their own, which cannot shadow the built-in ones.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use minijinja::{Environment, Error};
+use regex::Regex;
+
+use crate::db;
+
+/// Texts of the components available to `{% include %}`, by component id
+pub type ComponentLibrary = BTreeMap<String, String>;
+
+pub fn component_library<'a>(
+    components: impl IntoIterator<Item = &'a db::PromptComponent>,
+) -> ComponentLibrary {
+    components
+        .into_iter()
+        .map(|component| (component.component_id.0.to_string(), component.text.clone()))
+        .collect()
+}
+
+/// Variables every level defines
+pub const BUILTIN_VARIABLES: [&str; 4] = [
+    "LEVEL_NAME",
+    "LEVEL_PASSWORD",
+    "LEVEL_DIFFICULTY",
+    "USER_SUB",
+];
+
+/// Whether a level may define a custom variable with this name
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+        && !BUILTIN_VARIABLES.contains(&name)
+}
+
+/// Check a component text for syntax errors
+pub fn validate(text: &str) -> Result<(), Error> {
+    Environment::new().template_from_str(text).map(|_| ())
+}
+
+fn environment(
+    library: &ComponentLibrary,
+    level: &db::Level,
+    username: &str,
+) -> Environment<'static> {
+    let mut environment = Environment::new();
+    environment.set_keep_trailing_newline(true);
+
+    let library = Arc::new(library.clone());
+    environment.set_loader(move |name| Ok(library.get(name).cloned()));
+
+    for (name, value) in &level.variables {
+        environment.add_global(name.clone(), value.clone());
+    }
+
+    environment.add_global("LEVEL_NAME", level.name.clone());
+    environment.add_global("LEVEL_PASSWORD", level.password.clone());
+    environment.add_global(
+        "LEVEL_DIFFICULTY",
+        minijinja::Value::from_serialize(level.difficulty),
+    );
+    environment.add_global("USER_SUB", username.to_owned());
+
+    environment
+}
+
+/// Render each component for a chat of `username` on the level
+pub fn render_components(
+    components: &[String],
+    library: &ComponentLibrary,
+    level: &db::Level,
+    username: &str,
+) -> Result<Vec<String>, Error> {
+    let environment = environment(library, level, username);
+    components
+        .iter()
+        .map(|text| environment.render_str(text, ()))
+        .collect()
+}
+
+/// Variables used by the components, or the components they include, which are not defined
+/// for the level
+///
+/// Undefined variables render as empty strings, so they are easy to miss.
+/// Only includes naming a component literally are followed.
+pub fn undefined_variables(
+    components: &[String],
+    library: &ComponentLibrary,
+    level: &db::Level,
+) -> BTreeSet<String> {
+    let environment = environment(library, level, "");
+    let defined: BTreeSet<&str> = environment.globals().map(|(name, _)| name).collect();
+
+    let include = Regex::new(r#"\{%[-+]?\s*include\s+["']([^"']+)["']"#)
+        .expect("Include pattern is a valid regular expression");
+
+    let mut texts: Vec<&str> = components.iter().map(String::as_str).collect();
+    let mut included = BTreeSet::new();
+    let mut undefined = BTreeSet::new();
+    while let Some(text) = texts.pop() {
+        for name in include
+            .captures_iter(text)
+            .map(|captures| captures[1].to_owned())
+        {
+            if let Some(text) = library.get(&name)
+                && included.insert(name)
+            {
+                texts.push(text);
+            }
+        }
+
+        if let Ok(template) = environment.template_from_str(text) {
+            undefined.extend(
+                template
+                    .undeclared_variables(false)
+                    .into_iter()
+                    .filter(|name| !defined.contains(name.as_str())),
+            );
+        }
+    }
+
+    undefined
+}
@@ -13,29 +13,37 @@ pub trait ApiError: Debug {
     fn status_code(&self) -> StatusCode;
 }
 
-impl IntoResponse for BoxApiError {
-    fn into_response(self) -> axum::response::Response {
-        if self.status_code().is_server_error() {
+/// Body of every error returned by the API
+#[derive(Serialize, Debug)]
+pub struct ApiErrorResponse {
+    r#type: &'static str,
+    message: String,
+}
+
+impl ApiErrorResponse {
+    /// Log server errors and convert the error into its response body
+    pub fn from_error(error: &dyn ApiError) -> Self {
+        if error.status_code().is_server_error() {
             tracing::error!(
                 "[{}] {} | {:?}",
-                self.error_type(),
-                self.error_message(),
-                self
+                error.error_type(),
+                error.error_message(),
+                error
             );
         }
 
-        #[derive(Serialize)]
-        pub struct ApiErrorResponse {
-            r#type: &'static str,
-            message: String,
+        ApiErrorResponse {
+            r#type: error.error_type(),
+            message: error.error_message(),
         }
+    }
+}
 
+impl IntoResponse for BoxApiError {
+    fn into_response(self) -> axum::response::Response {
         (
             self.status_code(),
-            Json(ApiErrorResponse {
-                r#type: self.error_type(),
-                message: self.error_message(),
-            }),
+            Json(ApiErrorResponse::from_error(self.as_ref())),
         )
             .into_response()
     }
@@ -1,20 +1,16 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+};
 
-use aws_sdk_dynamodb::{operation::update_item::UpdateItemError, types::AttributeValue};
 use axum::{
     BoxError, Json, debug_handler,
-    extract::{FromRequest, Path},
+    extract::{FromRequest, Path, Query},
 };
-use itertools::Itertools;
+use regex::Regex;
 use serde::Deserialize;
-use serde_dynamo::{to_attribute_value, to_item};
 
-use crate::{
-    ExtractState,
-    auth::AuthorizedLevelManager,
-    db,
-    response::{ApiResult, MapBoxError},
-};
+use crate::{ExtractState, auth::AuthorizedLevelManager, render, routes::prompt::template_exists};
 
 use super::*;
 
@@ -35,15 +31,13 @@ pub async fn admin_get_levels(
     _: AuthorizedLevelManager,
     state: ExtractState,
 ) -> ApiResult<Json<AdminGetLevelsResponse>> {
-    let levels: Vec<db::Level> = state
-        .dynamo
-        .scan()
-        .table_name(db::Level::TABLE)
-        .send()
+    let mut levels = state
+        .repository
+        .get_levels()
         .await
         .box_error()
-        .and_then(|output| from_items(output.items.unwrap_or_default()).box_error())
-        .map_err(GetLevelsError::QueryLevels)?;
+        .map_err(AdminGetLevelsError::QueryLevels)?;
+    levels.retain(|level| level.deleted_at.is_none());
 
     Ok(Json(AdminGetLevelsResponse { levels }))
 }
@@ -69,7 +63,8 @@ pub async fn admin_create_level(
     state: ExtractState,
     request: CreateLevelRequest,
 ) -> ApiResult<Json<CreateLevelResponse>> {
-    let level_id = db::LevelID(db::Counter::increment(&state.dynamo, db::Counter::LEVEL_ID).await?);
+    let level_id =
+        db::LevelID(db::Counter::increment(&*state.repository, db::Counter::LEVEL_ID).await?);
 
     let password = {
         let mut hasher = DefaultHasher::default();
@@ -82,21 +77,23 @@ pub async fn admin_create_level(
         name: request.name,
         password,
         difficulty: db::LevelDifficulty::Low,
+        template_id: db::TemplateID::default(),
         prompt_components: Vec::new(),
+        prompt_layout: Vec::new(),
         is_root: false,
         next: Vec::new(),
+        is_open: false,
+        model_settings: db::ModelSettings::default(),
+        solve_on_leak: false,
+        input_guards: Vec::new(),
+        output_guards: Vec::new(),
+        variables: BTreeMap::new(),
+        deleted_at: None,
     };
 
     state
-        .dynamo
-        .put_item()
-        .table_name(db::Level::TABLE)
-        .set_item(Some(
-            to_item(&level)
-                .box_error()
-                .map_err(CreateLevelError::LevelCreation)?,
-        ))
-        .send()
+        .repository
+        .put_level(&level)
         .await
         .box_error()
         .map_err(CreateLevelError::LevelCreation)?;
@@ -110,14 +107,65 @@ pub struct ModifyLevelRequest {
     name: Option<String>,
     password: Option<String>,
     difficulty: Option<db::LevelDifficulty>,
+    template_id: Option<db::TemplateID>,
+    /// Components of the level's template, after applying `template_id`, in prompt order
     prompt_components: Option<Vec<crate::routes::prompt::ComponentID>>,
+    /// Components and inline text in prompt order, replaces `prompt_components`
+    prompt_layout: Option<Vec<db::PromptBlock>>,
     is_root: Option<bool>,
     next: Option<Vec<LevelID>>,
+    /// Let players chat without solving a preceding level first
+    is_open: Option<bool>,
+    model_settings: Option<db::ModelSettings>,
+    solve_on_leak: Option<bool>,
+    input_guards: Option<Vec<db::InputGuard>>,
+    output_guards: Option<Vec<db::OutputGuard>>,
+    /// Replaces all custom prompt variables of the level
+    variables: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModifyLevelQuery {
+    /// Reject `next` referencing levels which do not exist or leading back to the level
+    #[serde(default)]
+    strict: bool,
 }
 
 error_response!(ModifyLevelError {
     /// Level does not exist
     DoesNotExist[NOT_FOUND],
+    /// Model {model} is not available
+    UnknownModel[BAD_REQUEST] { model: String },
+    /// Temperature must be between 0 and 1
+    InvalidTemperature[BAD_REQUEST],
+    /// Maximum length must be between 1 and {maximum} tokens
+    InvalidMaximumLength[BAD_REQUEST] { maximum: u32 },
+    /// Invalid regular expression {pattern}
+    InvalidGuardPattern[BAD_REQUEST] { pattern: String },
+    /// Blocked keywords must not be empty, they would reject every message
+    EmptyGuardKeyword[BAD_REQUEST],
+    /// Maximum word count must be at least 1
+    InvalidMaximumWords[BAD_REQUEST],
+    /// Variable name {name} is reserved or not a valid identifier
+    InvalidVariableName[BAD_REQUEST] { name: String },
+    /// Next levels {levels} do not exist
+    DanglingNext[BAD_REQUEST] { levels: String },
+    /// Next levels would lead back to the level through levels {levels}
+    CyclicNext[BAD_REQUEST] { levels: String },
+    /// Only one of prompt_components and prompt_layout can be set
+    AmbiguousLayout[BAD_REQUEST],
+    /// Invalid template syntax in inline text: {error}
+    InvalidTemplate[BAD_REQUEST] { error: String },
+    /// Template {template_id} does not exist
+    UnknownTemplate[BAD_REQUEST] { template_id: String },
+    /// Component {component_id} is not part of the level's template
+    ComponentNotInTemplate[BAD_REQUEST] { component_id: u64 },
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to fetch next levels
+    QueryNext(BoxError),
     /// Failed to modify level
     LevelModification(BoxError)
 });
@@ -126,131 +174,458 @@ pub async fn admin_modify_level(
     _: AuthorizedLevelManager,
     state: ExtractState,
     Path(level_id): Path<LevelID>,
+    Query(query): Query<ModifyLevelQuery>,
     request: ModifyLevelRequest,
 ) -> ApiResult<()> {
-    let mut actions: Vec<(&str, (&str, AttributeValue))> = Vec::new();
-
-    if let Some(name) = request.name {
-        actions.push(("name", (db::Level::NAME, AttributeValue::S(name))));
-    }
-
-    if let Some(password) = request.password {
-        actions.push((
-            "password",
-            (db::Level::PASSWORD, AttributeValue::S(password)),
-        ));
-    }
-
-    if let Some(difficulty) = request.difficulty {
-        actions.push((
-            "difficulty",
-            (
-                db::Level::DIFFICULTY,
-                to_attribute_value(difficulty).expect("Cant convert attribute value to string"),
-            ),
-        ));
+    if let Some(model_settings) = &request.model_settings {
+        if let Some(model) = &model_settings.model
+            && !state.models.is_available(model)
+        {
+            return Err(ModifyLevelError::UnknownModel {
+                model: model.clone(),
+            }
+            .into());
+        }
+
+        if !(0.0..=1.0).contains(&model_settings.temperature) {
+            return Err(ModifyLevelError::InvalidTemperature.into());
+        }
+
+        let model = model_settings
+            .model
+            .as_deref()
+            .unwrap_or(state.models.default_model());
+        let maximum = state.models.maximum_length(model).unwrap_or(u32::MAX);
+        if !(1..=maximum).contains(&model_settings.maximum_length) {
+            return Err(ModifyLevelError::InvalidMaximumLength { maximum }.into());
+        }
     }
 
-    if let Some(prompt_components) = request.prompt_components {
-        actions.push((
-            "prompt_components",
-            (
-                db::Level::PROMPT_COMPONENTS,
-                to_attribute_value(
-                    prompt_components
-                        .into_iter()
-                        .map(|component| component.0)
-                        .collect_vec(),
-                )
-                .box_error()
-                .map_err(ModifyLevelError::LevelModification)?,
-            ),
-        ));
+    for guard in request.input_guards.iter().flatten() {
+        match guard {
+            db::InputGuard::KeywordBlocklist { keywords, .. }
+                if keywords.iter().any(|keyword| keyword.trim().is_empty()) =>
+            {
+                return Err(ModifyLevelError::EmptyGuardKeyword.into());
+            }
+            db::InputGuard::MaximumWords { maximum: 0, .. } => {
+                return Err(ModifyLevelError::InvalidMaximumWords.into());
+            }
+            db::InputGuard::Rewrite { pattern, .. } if Regex::new(pattern).is_err() => {
+                return Err(ModifyLevelError::InvalidGuardPattern {
+                    pattern: pattern.clone(),
+                }
+                .into());
+            }
+            _ => {}
+        }
     }
 
-    if let Some(is_root) = request.is_root {
-        actions.push((
-            "is_root",
-            (db::Level::IS_ROOT, AttributeValue::Bool(is_root)),
-        ));
+    for guard in request.output_guards.iter().flatten() {
+        match guard {
+            db::OutputGuard::Blocklist { patterns, .. } => {
+                let invalid = patterns.iter().find(|pattern| Regex::new(pattern).is_err());
+                if let Some(pattern) = invalid {
+                    return Err(ModifyLevelError::InvalidGuardPattern {
+                        pattern: pattern.clone(),
+                    }
+                    .into());
+                }
+            }
+            db::OutputGuard::Judge {
+                model: Some(model), ..
+            } if !state.models.is_available(model) => {
+                return Err(ModifyLevelError::UnknownModel {
+                    model: model.clone(),
+                }
+                .into());
+            }
+            _ => {}
+        }
     }
 
-    if let Some(next) = request.next {
-        actions.push((
-            "next",
-            (
-                db::Level::NEXT,
-                to_attribute_value(next.into_iter().map(|level| level.0).collect_vec())
-                    .box_error()
-                    .map_err(ModifyLevelError::LevelModification)?,
-            ),
-        ));
+    if let Some(name) = request
+        .variables
+        .iter()
+        .flat_map(|variables| variables.keys())
+        .find(|name| !render::is_valid_variable_name(name))
+    {
+        return Err(ModifyLevelError::InvalidVariableName { name: name.clone() }.into());
     }
 
-    if actions.is_empty() {
-        return Ok(());
+    if query.strict
+        && let Some(next) = &request.next
+    {
+        let mut levels = state
+            .repository
+            .get_levels()
+            .await
+            .box_error()
+            .map_err(ModifyLevelError::QueryNext)?;
+        levels.retain(|level| level.deleted_at.is_none());
+        let existing: HashSet<_> = levels.iter().map(|level| level.level_id).collect();
+
+        let dangling = next
+            .iter()
+            .filter(|next| !existing.contains(next))
+            .map(|next| next.0)
+            .join(", ");
+        if !dangling.is_empty() {
+            return Err(ModifyLevelError::DanglingNext { levels: dangling }.into());
+        }
+
+        if let Some(level) = levels.iter_mut().find(|level| level.level_id == level_id) {
+            level.next.clone_from(next);
+        }
+        if let Some(cycle) = graph::cycles_through(&levels, level_id).first() {
+            return Err(ModifyLevelError::CyclicNext {
+                levels: cycle.iter().map(|level_id| level_id.0).join(", "),
+            }
+            .into());
+        }
     }
 
-    let update_expression = format!(
-        "SET {}",
-        actions
+    let (prompt_components, prompt_layout) =
+        match (request.prompt_components, request.prompt_layout) {
+            (Some(_), Some(_)) => return Err(ModifyLevelError::AmbiguousLayout.into()),
+            (Some(components), None) => {
+                let layout = components
+                    .iter()
+                    .map(|&component_id| db::PromptBlock::Component { component_id })
+                    .collect();
+                (Some(components), Some(layout))
+            }
+            (None, Some(layout)) => {
+                for block in &layout {
+                    if let db::PromptBlock::Text { text } = block {
+                        render::validate(text).map_err(|err| {
+                            ModifyLevelError::InvalidTemplate {
+                                error: format!("{err:#}"),
+                            }
+                        })?;
+                    }
+                }
+
+                let components = layout
+                    .iter()
+                    .filter_map(|block| match block {
+                        db::PromptBlock::Component { component_id } => Some(*component_id),
+                        db::PromptBlock::Text { .. } => None,
+                    })
+                    .unique()
+                    .collect();
+                (Some(components), Some(layout))
+            }
+            (None, None) => (None, None),
+        };
+
+    if request.template_id.is_some() || prompt_components.is_some() {
+        let level = state
+            .repository
+            .get_level(level_id)
+            .await
+            .map_err(|err| match err {
+                RepositoryError::NotFound => ModifyLevelError::DoesNotExist,
+                RepositoryError::Backend(err) => ModifyLevelError::QueryLevel(err),
+            })?;
+
+        let template_id = request.template_id.as_ref().unwrap_or(&level.template_id);
+        if !template_exists(&*state.repository, template_id)
+            .await
+            .box_error()
+            .map_err(ModifyLevelError::QueryTemplate)?
+        {
+            return Err(ModifyLevelError::UnknownTemplate {
+                template_id: template_id.0.clone(),
+            }
+            .into());
+        }
+
+        let available = state
+            .repository
+            .get_components(template_id)
+            .await
+            .box_error()
+            .map_err(ModifyLevelError::QueryTemplate)?
+            .into_iter()
+            .filter(|component| component.deleted_at.is_none())
+            .map(|component| component.component_id)
+            .collect::<HashSet<_>>();
+
+        let components = prompt_components
+            .as_ref()
+            .unwrap_or(&level.prompt_components);
+        if let Some(component) = components
             .iter()
-            .map(|(placeholder, _)| format!("#{placeholder} = :{placeholder}"))
-            .join(" ,")
-    );
-
-    let mut update = state
-        .dynamo
-        .update_item()
-        .table_name(db::Level::TABLE)
-        .key(
-            db::Level::PARTITION,
-            AttributeValue::N(level_id.0.to_string()),
-        )
-        .condition_expression("#pk = :pk")
-        .expression_attribute_names("#pk", db::Level::PARTITION)
-        .expression_attribute_values(":pk", AttributeValue::N(level_id.0.to_string()))
-        .update_expression(update_expression);
-
-    for (placeholder, (key, value)) in actions {
-        update = update
-            .expression_attribute_names(["#", placeholder].concat(), key.to_owned())
-            .expression_attribute_values([":", placeholder].concat(), value);
+            .find(|component| !available.contains(component))
+        {
+            return Err(ModifyLevelError::ComponentNotInTemplate {
+                component_id: component.0,
+            }
+            .into());
+        }
     }
 
-    update
-        .send()
+    let update = db::LevelUpdate {
+        name: request.name,
+        password: request.password,
+        difficulty: request.difficulty,
+        template_id: request.template_id,
+        prompt_components,
+        prompt_layout,
+        is_root: request.is_root,
+        next: request.next,
+        is_open: request.is_open,
+        model_settings: request.model_settings,
+        solve_on_leak: request.solve_on_leak,
+        input_guards: request.input_guards,
+        output_guards: request.output_guards,
+        variables: request.variables,
+        deleted_at: None,
+    };
+
+    state
+        .repository
+        .update_level(level_id, update)
         .await
-        .map_err(|err| match err.into_service_error() {
-            UpdateItemError::ConditionalCheckFailedException(_) => ModifyLevelError::DoesNotExist,
-            err => ModifyLevelError::LevelModification(Box::new(err)),
+        .map_err(|err| match err {
+            RepositoryError::NotFound => ModifyLevelError::DoesNotExist,
+            RepositoryError::Backend(err) => ModifyLevelError::LevelModification(err),
         })?;
 
     Ok(())
 }
 
+#[derive(Deserialize, Debug)]
+pub struct DeleteLevelQuery {
+    /// Remove the level from the `next` of other levels instead of refusing to delete it
+    #[serde(default)]
+    cascade: bool,
+}
+
 error_response!(DeleteLevelError {
+    /// Level does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Level is still the next level of levels {levels}
+    LevelReferenced[CONFLICT] { levels: String },
+    /// Level is the next level of more than {maximum} levels, too many to update at once
+    TooManyReferences[CONFLICT] { maximum: usize },
+    /// Failed to fetch levels
+    QueryLevels(BoxError),
     /// Faield to delete level
     LevelDeletion(BoxError)
 });
 
+/// Move the level to the trash, its published versions stay until it is purged
 pub async fn admin_delete_level(
     _: AuthorizedLevelManager,
     state: ExtractState,
     Path(level_id): Path<LevelID>,
+    Query(query): Query<DeleteLevelQuery>,
 ) -> ApiResult<()> {
-    state
-        .dynamo
-        .delete_item()
-        .table_name(db::Level::TABLE)
-        .key(
-            db::Level::PARTITION,
-            AttributeValue::N(level_id.0.to_string()),
-        )
-        .send()
+    let levels: Vec<_> = state
+        .repository
+        .get_levels()
         .await
         .box_error()
-        .map_err(DeleteLevelError::LevelDeletion)?;
+        .map_err(DeleteLevelError::QueryLevels)?
+        .into_iter()
+        .filter(|level| level.deleted_at.is_none())
+        .collect();
+
+    if !levels.iter().any(|level| level.level_id == level_id) {
+        return Err(DeleteLevelError::DoesNotExist.into());
+    }
+
+    let references: Vec<_> = levels
+        .into_iter()
+        .filter(|level| level.level_id != level_id && level.next.contains(&level_id))
+        .collect();
+
+    if !query.cascade && !references.is_empty() {
+        let levels = references.iter().map(|level| level.level_id.0).join(", ");
+        return Err(DeleteLevelError::LevelReferenced { levels }.into());
+    }
+
+    // The level itself is changed in the same transaction
+    let maximum = db::MAX_TRANSACTION_ITEMS - 1;
+    if references.len() > maximum {
+        return Err(DeleteLevelError::TooManyReferences { maximum }.into());
+    }
+
+    let updates = references
+        .into_iter()
+        .map(|level| {
+            let next = level
+                .next
+                .into_iter()
+                .filter(|next| *next != level_id)
+                .collect();
+            let update = db::LevelUpdate {
+                next: Some(next),
+                ..Default::default()
+            };
+            (level.level_id, update)
+        })
+        .collect();
+
+    state
+        .repository
+        .trash_level(level_id, db::timestamp_now(), updates)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => DeleteLevelError::DoesNotExist,
+            RepositoryError::Backend(err) => DeleteLevelError::LevelDeletion(err),
+        })?;
 
     Ok(())
 }
+
+#[derive(Serialize, Debug)]
+pub struct PublishLevelResponse {
+    snapshot: db::PublishedLevel,
+}
+
+error_response!(PublishLevelError {
+    /// Level does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Level is in the trash, restore it before publishing
+    LevelDeleted[CONFLICT],
+    /// Level was published by someone else at the same time
+    ConcurrentPublication[CONFLICT],
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Failed to publish level
+    LevelPublication(BoxError)
+});
+
+/// Publish the level's draft together with its resolved prompt as a new immutable version
+pub async fn admin_publish_level(
+    manager: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(level_id): Path<LevelID>,
+) -> ApiResult<Json<PublishLevelResponse>> {
+    let level = state
+        .repository
+        .get_level(level_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => PublishLevelError::DoesNotExist,
+            RepositoryError::Backend(err) => PublishLevelError::QueryLevel(err),
+        })?;
+    if level.deleted_at.is_some() {
+        return Err(PublishLevelError::LevelDeleted.into());
+    }
+
+    let version = match state.repository.get_published_level(level_id).await {
+        Ok(published) => published.version + 1,
+        Err(RepositoryError::NotFound) => 1,
+        Err(RepositoryError::Backend(err)) => {
+            return Err(PublishLevelError::QueryLevel(err).into());
+        }
+    };
+
+    let snapshot = level_snapshot(&*state.repository, level, version, manager.username())
+        .await
+        .box_error()
+        .map_err(PublishLevelError::QueryComponents)?;
+
+    let published = state
+        .repository
+        .publish_level(&snapshot)
+        .await
+        .box_error()
+        .map_err(PublishLevelError::LevelPublication)?;
+    if !published {
+        return Err(PublishLevelError::ConcurrentPublication.into());
+    }
+
+    Ok(Json(PublishLevelResponse { snapshot }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct PublishUnpublishedLevelsResponse {
+    /// Levels published by this request
+    published: Vec<LevelID>,
+}
+
+error_response!(PublishUnpublishedLevelsError {
+    /// Failed to fetch levels
+    QueryLevels(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Failed to publish level
+    LevelPublication(BoxError)
+});
+
+/// Publish the draft of every level outside the trash which has no published version yet
+///
+/// Players only see published levels, so levels created before publishing existed
+/// stay hidden until this is run once after deploying.
+pub async fn admin_publish_unpublished_levels(
+    manager: AuthorizedLevelManager,
+    state: ExtractState,
+) -> ApiResult<Json<PublishUnpublishedLevelsResponse>> {
+    let published: HashSet<_> = state
+        .repository
+        .get_published_levels()
+        .await
+        .box_error()
+        .map_err(PublishUnpublishedLevelsError::QueryLevels)?
+        .into_iter()
+        .map(|snapshot| snapshot.level_id)
+        .collect();
+
+    let unpublished = state
+        .repository
+        .get_levels()
+        .await
+        .box_error()
+        .map_err(PublishUnpublishedLevelsError::QueryLevels)?
+        .into_iter()
+        .filter(|level| level.deleted_at.is_none() && !published.contains(&level.level_id));
+
+    let mut published = Vec::new();
+    for level in unpublished {
+        let level_id = level.level_id;
+        let snapshot = level_snapshot(&*state.repository, level, 1, manager.username())
+            .await
+            .box_error()
+            .map_err(PublishUnpublishedLevelsError::QueryComponents)?;
+
+        // Otherwise someone else published the level in the meantime
+        if state
+            .repository
+            .publish_level(&snapshot)
+            .await
+            .box_error()
+            .map_err(PublishUnpublishedLevelsError::LevelPublication)?
+        {
+            published.push(level_id);
+        }
+    }
+
+    Ok(Json(PublishUnpublishedLevelsResponse { published }))
+}
+
+/// Version of the level with its draft and resolved prompt
+async fn level_snapshot(
+    repository: &dyn db::Repository,
+    level: db::Level,
+    version: u64,
+    published_by: &str,
+) -> db::RepositoryResult<db::PublishedLevel> {
+    let (prompt, library) = level_prompt(repository, &level).await?;
+
+    Ok(db::PublishedLevel {
+        level_id: level.level_id,
+        version,
+        published_at: db::timestamp_now(),
+        published_by: published_by.to_owned(),
+        level,
+        prompt,
+        library,
+    })
+}
@@ -0,0 +1,124 @@
+use axum::extract::{Path, Query};
+
+use super::*;
+use crate::{auth::AuthorizedLevelManager, llm, render};
+
+#[derive(Deserialize, Debug)]
+pub struct PromptAssemblyQuery {
+    /// Player to render `USER_SUB` for
+    #[serde(default = "default_username")]
+    username: String,
+    /// Render the published version instead of the draft
+    #[serde(default)]
+    published: bool,
+}
+
+fn default_username() -> String {
+    "player".to_owned()
+}
+
+#[derive(Serialize, Debug)]
+pub struct PromptAssemblyResponse {
+    model: String,
+    /// Texts of the prompt components in prompt order
+    components: Vec<String>,
+    /// Components rendered with the level's variables and includes
+    rendered: Vec<String>,
+    /// Rendered components joined with spaces, as passed to the model provider
+    instruction: String,
+    /// Instruction padded to the minimum length, only for Bedrock models
+    padded: Option<String>,
+    /// Orchestration prompt template Bedrock inserts the padded instruction into
+    base_prompt: Option<serde_json::Value>,
+    /// Problems found while assembling, like unknown placeholders
+    warnings: Vec<String>,
+}
+
+error_response!(PromptAssemblyError {
+    /// Level does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to render prompt: {error}
+    RenderPrompt[BAD_REQUEST] { error: String },
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError)
+});
+
+/// Show every stage of building the instruction a chat on the level would send to the model
+pub async fn admin_get_prompt_assembly(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(level_id): Path<LevelID>,
+    Query(query): Query<PromptAssemblyQuery>,
+) -> ApiResult<Json<PromptAssemblyResponse>> {
+    let map_level_error = |err| match err {
+        RepositoryError::NotFound => PromptAssemblyError::DoesNotExist,
+        RepositoryError::Backend(err) => PromptAssemblyError::QueryLevel(err),
+    };
+
+    let (level, components, library) = if query.published {
+        let published = state
+            .repository
+            .get_published_level(level_id)
+            .await
+            .map_err(map_level_error)?;
+        (published.level, published.prompt, published.library)
+    } else {
+        let level = state
+            .repository
+            .get_level(level_id)
+            .await
+            .map_err(map_level_error)?;
+        let (components, library) = level_prompt(&*state.repository, &level)
+            .await
+            .box_error()
+            .map_err(PromptAssemblyError::QueryComponents)?;
+        (level, components, library)
+    };
+
+    let model = level
+        .model_settings
+        .model
+        .clone()
+        .unwrap_or_else(|| state.models.default_model().to_owned());
+
+    let rendered = render::render_components(&components, &library, &level, &query.username)
+        .map_err(|err| PromptAssemblyError::RenderPrompt {
+            error: format!("{err:#}"),
+        })?;
+    let instruction = rendered.join(" ");
+
+    let mut warnings: Vec<String> = render::undefined_variables(&components, &library, &level)
+        .into_iter()
+        .map(|name| format!("Undefined variable {name} renders as an empty string"))
+        .collect();
+
+    let (padded, base_prompt) = match llm::split_model(&model) {
+        ("bedrock", _) => {
+            let padded = llm::pad_instruction(&instruction);
+            if padded.len() > instruction.len() {
+                warnings.push(format!(
+                    "Instruction is shorter than {} bytes and gets padded with spaces",
+                    llm::MIN_INSTRUCTION_LENGTH
+                ));
+            }
+            (Some(padded), Some(llm::base_prompt()))
+        }
+        _ => (None, None),
+    };
+
+    if !state.models.is_available(&model) {
+        warnings.push(format!("Model {model} is not available"));
+    }
+
+    Ok(Json(PromptAssemblyResponse {
+        model,
+        components,
+        rendered,
+        instruction,
+        padded,
+        base_prompt,
+        warnings,
+    }))
+}
@@ -1,31 +1,27 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::convert::Infallible;
 
-use aws_sdk_bedrockagentruntime::types::{
-    CreationMode, InferenceConfiguration, InlineAgentPayloadPart, InlineAgentResponseStream,
-    PromptConfiguration, PromptOverrideConfiguration, PromptState, PromptType,
-};
-use aws_sdk_dynamodb::types::AttributeValue;
 use axum::{
     BoxError,
     extract::{FromRequest, Json, Path},
+    response::{
+        Response,
+        sse::{Event, Sse},
+    },
 };
-use indoc::indoc;
-use itertools::Itertools;
+use futures::{Stream, StreamExt, stream};
+use jb_common::tracing;
 use serde::{Deserialize, Serialize};
-use serde_dynamo::from_item;
-use serde_json::json;
 
 use super::*;
-use crate::{ExtractState, response::ApiResult};
+use crate::{
+    ExtractState,
+    auth::AuthorizedPlayer,
+    llm, render,
+    response::{ApiErrorResponse, ApiResult},
+};
 
-const MIN_INSTRUCTION_LENGTH: usize = 40;
 const MAXIMUM_MESSAGE_LENGTH: usize = 500;
 
-#[derive(Deserialize, Debug)]
-pub struct UserInfo {
-    username: String,
-}
-
 #[derive(Deserialize, FromRequest, Debug)]
 #[from_request(via(Json))]
 pub struct ChatRequest {
@@ -36,6 +32,8 @@ pub struct ChatRequest {
 #[derive(Serialize, Debug)]
 pub struct ChatReply {
     reply: String,
+    /// The reply leaked the password and the level counts as solved
+    level_solved: bool,
 }
 
 error_response!(ChatError {
@@ -43,199 +41,261 @@ error_response!(ChatError {
     LevelDoesNotExist[NOT_FOUND],
     /// Maximum prompt size exceeded
     PromptTooLarge[PAYLOAD_TOO_LARGE],
+    /// {rejection}
+    InputRejected[BAD_REQUEST] { rejection: String },
     /// Fetching level failed
     GetLevel(BoxError),
-    /// Failed to fetch prompt components
-    QueryPromptComponents(BoxError),
-    /// Failed to invoke bedrock
-    BedrockInvocationFailed(BoxError),
-    /// Model responded with Non-UTF8 characters
-    IllegalModelResponse,
+    /// Failed to render prompt
+    RenderPrompt(BoxError),
 });
 
+/// Everything needed to send a player message to the level's model
+pub(super) struct PreparedChat {
+    pub(super) level: db::Level,
+    username: String,
+    /// Solves are only recorded for signed in players, guests could claim any username
+    signed_in: bool,
+    client_session_id: String,
+    pub(super) session_id: String,
+    pub(super) instruction: String,
+    message: String,
+}
+
+impl PreparedChat {
+    /// Prepare a chat with the published version of a level
+    ///
+    /// Guests are only known by the username they send along,
+    /// so levels are unlocked by the verified username alone.
+    async fn prepare(
+        state: &crate::State,
+        player: Result<AuthorizedPlayer, Response>,
+        level_id: LevelID,
+        session_id: String,
+        ChatRequest { message, user_info }: ChatRequest,
+    ) -> ApiResult<Self> {
+        let published =
+            playable_level(&*state.repository, level_id)
+                .await
+                .map_err(|err| match err {
+                    RepositoryError::NotFound => ChatError::LevelDoesNotExist,
+                    RepositoryError::Backend(err) => ChatError::GetLevel(err),
+                })?;
+
+        let verified = player.as_ref().ok().map(AuthorizedPlayer::username);
+        ensure_unlocked(state, &published.level, verified).await?;
+
+        let signed_in = player.is_ok();
+        let (owner, username) = match player {
+            Ok(player) => (player.username().to_owned(), player.username().to_owned()),
+            Err(_) => (
+                db::ChatSession::guest_username(&user_info.username),
+                user_info.username,
+            ),
+        };
+
+        let chat = Self::new(
+            published.level,
+            &published.prompt,
+            &published.library,
+            owner,
+            session_id,
+            &username,
+            message,
+        )?;
+        Ok(Self { signed_in, ..chat })
+    }
+
+    /// Prepare a chat with any version of a level and its prompt
+    ///
+    /// The session is stored under `owner`, while the prompt is rendered for `username`.
+    pub(super) fn new(
+        level: db::Level,
+        prompt: &[String],
+        library: &render::ComponentLibrary,
+        owner: String,
+        session_id: String,
+        username: &str,
+        message: String,
+    ) -> Result<Self, ChatError> {
+        if message.len() > MAXIMUM_MESSAGE_LENGTH {
+            return Err(ChatError::PromptTooLarge);
+        }
+
+        let message = guard::apply_input_guards(&level, message)
+            .map_err(|rejection| ChatError::InputRejected { rejection })?;
+
+        let instruction = render::render_components(prompt, library, &level, username)
+            .box_error()
+            .map_err(ChatError::RenderPrompt)?
+            .join(" ");
+
+        Ok(PreparedChat {
+            session_id: db::ChatSession::id_for(&owner, level.level_id, &session_id),
+            level,
+            client_session_id: session_id,
+            username: owner,
+            signed_in: false,
+            instruction,
+            message,
+        })
+    }
+
+    pub(super) fn model<'a>(&'a self, models: &'a llm::ChatModels) -> &'a str {
+        self.level
+            .model_settings
+            .model
+            .as_deref()
+            .unwrap_or(models.default_model())
+    }
+
+    pub(super) fn completion_request(&self) -> llm::CompletionRequest<'_> {
+        let settings = &self.level.model_settings;
+        llm::CompletionRequest {
+            session_id: &self.session_id,
+            instruction: &self.instruction,
+            message: &self.message,
+            temperature: settings.temperature,
+            maximum_length: settings.maximum_length,
+            stop_sequences: &settings.stop_sequences,
+        }
+    }
+
+    /// Append the turn to the session's transcript and check the reply for password leaks
+    ///
+    /// Returns whether the level was solved by the leak.
+    /// The player already paid for the reply, so failures are only logged.
+    async fn record(self, state: &crate::State, reply: String) -> bool {
+        let leak = leak::detect(&self.level.password, &reply);
+        let timestamp = db::timestamp_now();
+
+        let level_solved = leak.is_some() && self.level.solve_on_leak;
+
+        let turn = db::NewChatTurn {
+            session_id: self.session_id,
+            client_session_id: self.client_session_id,
+            level_id: self.level.level_id,
+            username: self.username.clone(),
+            message: self.message,
+            reply,
+            timestamp,
+            leak,
+        };
+
+        if let Err(err) = state.repository.record_chat_turn(turn).await {
+            tracing::error!("Failed to record chat turn: {err}");
+        }
+
+        // Recorded after the turn, so the leaking message counts towards the solve
+        if level_solved && self.signed_in {
+            record_solve(&*state.repository, self.username, self.level.level_id).await;
+        }
+
+        level_solved
+    }
+}
+
 #[axum::debug_handler(state=crate::State)]
 pub async fn chat_session(
     state: ExtractState,
+    player: Result<AuthorizedPlayer, Response>,
     Path((level_id, session_id)): Path<(LevelID, String)>,
-    ChatRequest { message, user_info }: ChatRequest,
+    request: ChatRequest,
 ) -> ApiResult<Json<ChatReply>> {
-    if message.len() > MAXIMUM_MESSAGE_LENGTH {
-        return Err(ChatError::PromptTooLarge.into());
-    }
+    let chat = PreparedChat::prepare(&state, player, level_id, session_id, request).await?;
 
-    let level = state
-        .dynamo
-        .get_item()
-        .table_name(db::Level::TABLE)
-        .key(
-            db::Level::PARTITION,
-            AttributeValue::N(level_id.0.to_string()),
-        )
-        .send()
-        .await
-        .box_error()
-        .map_err(ChatError::GetLevel)?
-        .item
-        .ok_or(ChatError::LevelDoesNotExist)?;
-
-    let level: db::Level = from_item(level).box_error().map_err(ChatError::GetLevel)?;
-
-    let components = prompt_components_for_level(&state.dynamo, &level).await?;
-
-    let mut instruction = components
-        .into_iter()
-        .map(|component| component.text)
-        .join(" ")
-        .replace("{{LEVEL_NAME}}", &level.name)
-        .replace("{{LEVEL_PASSWORD}}", &level.password)
-        .replace("{{USER_SUB}}", &user_info.username);
-
-    if instruction.len() < MIN_INSTRUCTION_LENGTH {
-        instruction.push_str(&" ".repeat(MIN_INSTRUCTION_LENGTH - instruction.len()));
-    }
+    let reply = state
+        .models
+        .complete(chat.model(&state.models), chat.completion_request())
+        .await?;
+    let reply =
+        guard::apply_output_guards(&state.models, &chat.level, &chat.session_id, reply).await?;
 
-    let base_prompt = json!({
-        "system": indoc!("
-            ALWAYS provide your final response to the User request within <answer> </answer> tags! Do not use it to ask questions!
-            Additionally, follow these instructions:
-            $instruction$
-        "),
-        "messages": [
-            {
-                "role" : "user",
-                "content": [{
-                    "text": "$question$"
-                }]
-            },
-            //{
-            //    "role" : "assistant",
-            //    "content" : [{
-            //        "text": "Response: <answer>"
-            //    }]
-            //}
-        ]
-    });
-
-    let final_session_id = {
-        let mut hasher = DefaultHasher::default();
-        user_info.username.hash(&mut hasher);
-        session_id.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    };
-
-    let mut response = state
-        .bedrockagent
-        .invoke_inline_agent()
-        .session_id(final_session_id)
-        .idle_session_ttl_in_seconds(120)
-        .foundation_model("eu.meta.llama3-2-3b-instruct-v1:0")
-        .prompt_override_configuration(
-            PromptOverrideConfiguration::builder()
-                .prompt_configurations(
-                    PromptConfiguration::builder()
-                        .prompt_type(PromptType::PreProcessing)
-                        .prompt_state(PromptState::Disabled)
-                        .build(),
-                )
-                .prompt_configurations(
-                    PromptConfiguration::builder()
-                        .prompt_type(PromptType::KnowledgeBaseResponseGeneration)
-                        .prompt_state(PromptState::Disabled)
-                        .build(),
-                )
-                .prompt_configurations(
-                    PromptConfiguration::builder()
-                        .prompt_type(PromptType::PostProcessing)
-                        .prompt_state(PromptState::Disabled)
-                        .build(),
-                )
-                .prompt_configurations(
-                    PromptConfiguration::builder()
-                        .prompt_type(PromptType::Orchestration)
-                        .prompt_state(PromptState::Enabled)
-                        .parser_mode(CreationMode::Default)
-                        .inference_configuration(
-                            InferenceConfiguration::builder()
-                                .maximum_length(150)
-                                .temperature(0.9)
-                                .stop_sequences("</answer>")
-                                .build(),
-                        )
-                        .prompt_creation_mode(CreationMode::Overridden)
-                        .base_prompt_template(base_prompt.to_string())
-                        .build(),
-                )
-                .build()
-                .unwrap(),
-        )
-        .instruction(instruction)
-        .input_text(message)
-        .send()
-        .await
-        .map_err(|e| ChatError::BedrockInvocationFailed(Box::new(e)))?;
-
-    let mut reply_stream: Vec<u8> = Vec::new();
-    while let Some(chunk) = response
-        .completion
-        .recv()
-        .await
-        .map_err(|e| ChatError::BedrockInvocationFailed(Box::new(e)))?
-    {
-        if let InlineAgentResponseStream::Chunk(InlineAgentPayloadPart {
-            bytes: Some(chunk), ..
-        }) = chunk
-        {
-            reply_stream.extend(chunk.as_ref());
-        }
-    }
+    let level_solved = chat.record(&state, reply.clone()).await;
 
-    let reply = String::from_utf8(reply_stream).map_err(|_| ChatError::IllegalModelResponse)?;
+    Ok(Json(ChatReply {
+        reply,
+        level_solved,
+    }))
+}
 
-    Ok(Json(ChatReply { reply }))
+#[derive(Serialize, Debug)]
+pub struct ChatReplyChunk {
+    text: String,
 }
 
-async fn prompt_components_for_level(
-    dynamo: &aws_sdk_dynamodb::Client,
-    level: &db::Level,
-) -> ApiResult<Vec<db::PromptComponent>> {
-    if level.prompt_components.is_empty() {
-        return Ok(Vec::new());
-    }
+fn sse_event(name: &'static str, data: impl Serialize) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .expect("Unable to serialize server-sent event")
+}
 
-    let component_ids = level
-        .prompt_components
-        .iter()
-        .map(|component| component.0.to_string());
-
-    let mut components_query = dynamo
-        .query()
-        .table_name(db::PromptComponent::TABLE)
-        .index_name(db::PromptComponent::SECONDARY_TEMPLATE_INDEX)
-        .key_condition_expression("#pk = :pk")
-        .expression_attribute_names("#pk", db::PromptComponent::SECONDARY_TEMPLATE_ID)
-        .expression_attribute_values(":pk", AttributeValue::S(db::TemplateID::default().0));
-
-    let mut placeholders = Vec::new();
-    for id in component_ids {
-        let placeholder = format!(":{id}");
-        components_query = components_query
-            .expression_attribute_values(&placeholder, AttributeValue::N(id.to_string()));
-        placeholders.push(placeholder);
-    }
+/// Like [`chat_session`], but forwards the reply as server-sent events while it is generated
+///
+/// Each `chunk` event carries a [`ChatReplyChunk`], the stream ends with either
+/// a `done` event carrying the complete [`ChatReply`] or an `error` event.
+/// Levels with output guards only send the `done` event, as the guards need the complete reply.
+#[axum::debug_handler(state=crate::State)]
+pub async fn chat_session_stream(
+    state: ExtractState,
+    player: Result<AuthorizedPlayer, Response>,
+    Path((level_id, session_id)): Path<(LevelID, String)>,
+    request: ChatRequest,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let chat = PreparedChat::prepare(&state, player, level_id, session_id, request).await?;
+
+    let reply_stream = state
+        .models
+        .stream(chat.model(&state.models), chat.completion_request())
+        .await?;
+
+    let events = stream::unfold(
+        Some((reply_stream, String::new(), chat, state)),
+        |progress| async move {
+            let (mut reply_stream, mut reply, chat, state) = progress?;
+            let is_guarded = !chat.level.output_guards.is_empty();
+
+            let event = loop {
+                match reply_stream.next().await {
+                    Some(Ok(text)) => {
+                        reply.push_str(&text);
+                        if is_guarded {
+                            continue;
+                        }
+                        let event = sse_event("chunk", ChatReplyChunk { text });
+                        return Some((Ok(event), Some((reply_stream, reply, chat, state))));
+                    }
+                    Some(Err(err)) => break sse_event("error", ApiErrorResponse::from_error(&err)),
+                    None => {
+                        let guarded = guard::apply_output_guards(
+                            &state.models,
+                            &chat.level,
+                            &chat.session_id,
+                            reply,
+                        )
+                        .await;
+                        let reply = match guarded {
+                            Ok(reply) => reply,
+                            Err(err) => {
+                                break sse_event("error", ApiErrorResponse::from_error(&err));
+                            }
+                        };
+
+                        let level_solved = chat.record(&state, reply.clone()).await;
+                        break sse_event(
+                            "done",
+                            ChatReply {
+                                reply,
+                                level_solved,
+                            },
+                        );
+                    }
+                }
+            };
+
+            Some((Ok(event), None))
+        },
+    );
 
-    components_query = components_query
-        .filter_expression(format!(
-            "#component IN ({placeholders})",
-            placeholders = placeholders.join(",")
-        ))
-        .expression_attribute_names("#component", db::PromptComponent::PARTITION);
-
-    let components: Vec<db::PromptComponent> = components_query
-        .send()
-        .await
-        .box_error()
-        .and_then(|output| from_items(output.items.unwrap_or_default()).box_error())
-        .map_err(ChatError::QueryPromptComponents)?;
-
-    Ok(components)
+    Ok(Sse::new(events))
 }
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::auth::AuthorizedLevelManager;
+
+use super::*;
+
+/// Problem found in the graph formed by `is_root` and `next` of all levels
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GraphProblem {
+    /// No level is a root, so new players cannot start anywhere
+    NoRoots,
+    /// A level continues with a level that does not exist
+    DanglingEdge { level_id: LevelID, next: LevelID },
+    /// No path from a root or open level leads to the level
+    Unreachable { level_id: LevelID },
+    /// The levels lead back to themselves, a single level is a self-loop
+    Cycle { levels: Vec<LevelID> },
+    /// A level continues with an easier one
+    DifficultyRegression {
+        level_id: LevelID,
+        next: LevelID,
+        from: LevelDifficulty,
+        to: LevelDifficulty,
+    },
+}
+
+/// Find all problems in the level graph, ordered by kind and level
+pub fn analyze(levels: &[db::Level]) -> Vec<GraphProblem> {
+    let levels: BTreeMap<_, _> = levels.iter().map(|level| (level.level_id, level)).collect();
+    let mut problems = Vec::new();
+
+    if !levels.values().any(|level| level.is_root) {
+        problems.push(GraphProblem::NoRoots);
+    }
+
+    for level in levels.values() {
+        for next in &level.next {
+            if !levels.contains_key(next) {
+                problems.push(GraphProblem::DanglingEdge {
+                    level_id: level.level_id,
+                    next: *next,
+                });
+            }
+        }
+    }
+
+    let mut reachable = BTreeSet::new();
+    let mut pending: Vec<_> = levels
+        .values()
+        .filter(|level| level.is_root || level.is_open)
+        .map(|level| level.level_id)
+        .collect();
+    while let Some(level_id) = pending.pop() {
+        if let Some(level) = levels.get(&level_id)
+            && reachable.insert(level_id)
+        {
+            pending.extend(level.next.iter().copied());
+        }
+    }
+    problems.extend(
+        levels
+            .keys()
+            .filter(|level_id| !reachable.contains(level_id))
+            .map(|&level_id| GraphProblem::Unreachable { level_id }),
+    );
+
+    problems.extend(
+        cycles(&levels)
+            .into_iter()
+            .map(|levels| GraphProblem::Cycle { levels }),
+    );
+
+    for level in levels.values() {
+        for next in level.next.iter().filter_map(|next| levels.get(next)) {
+            if next.difficulty < level.difficulty {
+                problems.push(GraphProblem::DifficultyRegression {
+                    level_id: level.level_id,
+                    next: next.level_id,
+                    from: level.difficulty,
+                    to: next.difficulty,
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Cycles which include the given level
+pub fn cycles_through(levels: &[db::Level], level_id: LevelID) -> Vec<Vec<LevelID>> {
+    let levels = levels.iter().map(|level| (level.level_id, level)).collect();
+    cycles(&levels)
+        .into_iter()
+        .filter(|cycle| cycle.contains(&level_id))
+        .collect()
+}
+
+/// Strongly connected components with a cycle, found with Tarjan's algorithm
+fn cycles(levels: &BTreeMap<LevelID, &db::Level>) -> Vec<Vec<LevelID>> {
+    struct Search<'a> {
+        levels: &'a BTreeMap<LevelID, &'a db::Level>,
+        index: BTreeMap<LevelID, usize>,
+        low_link: BTreeMap<LevelID, usize>,
+        stack: Vec<LevelID>,
+        on_stack: BTreeSet<LevelID>,
+        cycles: Vec<Vec<LevelID>>,
+    }
+
+    impl Search<'_> {
+        fn visit(&mut self, level_id: LevelID) {
+            let index = self.index.len();
+            self.index.insert(level_id, index);
+            self.low_link.insert(level_id, index);
+            self.stack.push(level_id);
+            self.on_stack.insert(level_id);
+
+            let level = self.levels[&level_id];
+            for next in level
+                .next
+                .iter()
+                .filter(|next| self.levels.contains_key(next))
+            {
+                if !self.index.contains_key(next) {
+                    self.visit(*next);
+                    let low_link = self.low_link[&level_id].min(self.low_link[next]);
+                    self.low_link.insert(level_id, low_link);
+                } else if self.on_stack.contains(next) {
+                    let low_link = self.low_link[&level_id].min(self.index[next]);
+                    self.low_link.insert(level_id, low_link);
+                }
+            }
+
+            if self.low_link[&level_id] == index {
+                let position = self
+                    .stack
+                    .iter()
+                    .position(|member| *member == level_id)
+                    .expect("Visited level is on the stack");
+                let mut component = self.stack.split_off(position);
+                for member in &component {
+                    self.on_stack.remove(member);
+                }
+
+                if component.len() > 1 || level.next.contains(&level_id) {
+                    component.sort();
+                    self.cycles.push(component);
+                }
+            }
+        }
+    }
+
+    let mut search = Search {
+        levels,
+        index: BTreeMap::new(),
+        low_link: BTreeMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        cycles: Vec::new(),
+    };
+    for &level_id in levels.keys() {
+        if !search.index.contains_key(&level_id) {
+            search.visit(level_id);
+        }
+    }
+
+    search.cycles.sort();
+    search.cycles
+}
+
+#[derive(Serialize, Debug)]
+pub struct LevelGraphResponse {
+    /// Levels new players start at
+    roots: Vec<LevelID>,
+    problems: Vec<GraphProblem>,
+}
+
+error_response!(LevelGraphError {
+    /// Failed to fetch levels
+    QueryLevels(BoxError)
+});
+
+/// Check the graph formed by the draft versions of all levels outside the trash
+pub async fn admin_get_level_graph(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+) -> ApiResult<Json<LevelGraphResponse>> {
+    let mut levels = state
+        .repository
+        .get_levels()
+        .await
+        .box_error()
+        .map_err(LevelGraphError::QueryLevels)?;
+    levels.retain(|level| level.deleted_at.is_none());
+
+    let roots = levels
+        .iter()
+        .filter(|level| level.is_root)
+        .map(|level| level.level_id)
+        .sorted()
+        .collect();
+
+    Ok(Json(LevelGraphResponse {
+        roots,
+        problems: analyze(&levels),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(id: u64, difficulty: LevelDifficulty, next: &[u64]) -> db::Level {
+        db::Level {
+            level_id: LevelID(id),
+            name: format!("Level {id}"),
+            password: "secret".to_owned(),
+            difficulty,
+            template_id: db::TemplateID::default(),
+            prompt_components: Vec::new(),
+            prompt_layout: Vec::new(),
+            is_root: false,
+            next: next.iter().copied().map(LevelID).collect(),
+            is_open: false,
+            model_settings: db::ModelSettings::default(),
+            solve_on_leak: false,
+            input_guards: Vec::new(),
+            output_guards: Vec::new(),
+            variables: Default::default(),
+            deleted_at: None,
+        }
+    }
+
+    fn root(id: u64, next: &[u64]) -> db::Level {
+        db::Level {
+            is_root: true,
+            ..level(id, LevelDifficulty::Low, next)
+        }
+    }
+
+    #[test]
+    fn sound_graph() {
+        let levels = [
+            root(1, &[2, 3]),
+            level(2, LevelDifficulty::Medium, &[3]),
+            level(3, LevelDifficulty::Medium, &[]),
+        ];
+        assert_eq!(analyze(&levels), []);
+        assert_eq!(analyze(&[]), [GraphProblem::NoRoots]);
+    }
+
+    #[test]
+    fn no_roots() {
+        let levels = [
+            level(1, LevelDifficulty::Low, &[2]),
+            level(2, LevelDifficulty::Low, &[]),
+        ];
+        assert_eq!(
+            analyze(&levels),
+            [
+                GraphProblem::NoRoots,
+                GraphProblem::Unreachable {
+                    level_id: LevelID(1)
+                },
+                GraphProblem::Unreachable {
+                    level_id: LevelID(2)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_edge() {
+        let levels = [root(1, &[7])];
+        assert_eq!(
+            analyze(&levels),
+            [GraphProblem::DanglingEdge {
+                level_id: LevelID(1),
+                next: LevelID(7)
+            }]
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let open = db::Level {
+            is_open: true,
+            ..level(3, LevelDifficulty::Low, &[4])
+        };
+        let levels = [
+            root(1, &[]),
+            level(2, LevelDifficulty::Low, &[1]),
+            open,
+            level(4, LevelDifficulty::Low, &[]),
+        ];
+        assert_eq!(
+            analyze(&levels),
+            [GraphProblem::Unreachable {
+                level_id: LevelID(2)
+            }]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let levels = [
+            root(1, &[2]),
+            level(2, LevelDifficulty::Low, &[3]),
+            level(3, LevelDifficulty::Low, &[2, 4]),
+            level(4, LevelDifficulty::Low, &[4]),
+        ];
+        assert_eq!(
+            analyze(&levels),
+            [
+                GraphProblem::Cycle {
+                    levels: vec![LevelID(2), LevelID(3)]
+                },
+                GraphProblem::Cycle {
+                    levels: vec![LevelID(4)]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cycles_through_level() {
+        let levels = [
+            root(1, &[2]),
+            level(2, LevelDifficulty::Low, &[1]),
+            level(3, LevelDifficulty::Low, &[3]),
+        ];
+
+        assert_eq!(
+            cycles_through(&levels, LevelID(1)),
+            [[LevelID(1), LevelID(2)]]
+        );
+        assert_eq!(cycles_through(&levels, LevelID(3)), [[LevelID(3)]]);
+        assert!(cycles_through(&levels[..1], LevelID(1)).is_empty());
+    }
+
+    #[test]
+    fn difficulty_regression() {
+        let levels = [
+            root(1, &[2]),
+            level(2, LevelDifficulty::High, &[3]),
+            level(3, LevelDifficulty::Medium, &[]),
+        ];
+        assert_eq!(
+            analyze(&levels),
+            [GraphProblem::DifficultyRegression {
+                level_id: LevelID(2),
+                next: LevelID(3),
+                from: LevelDifficulty::High,
+                to: LevelDifficulty::Medium,
+            }]
+        );
+    }
+}
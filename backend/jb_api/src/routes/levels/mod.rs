@@ -1,18 +1,32 @@
+use std::collections::{BTreeSet, HashSet};
+
 use axum::{BoxError, Json};
-use serde::Serialize;
-use serde_dynamo::from_items;
+use itertools::Itertools;
+use jb_common::tracing;
+use serde::{Deserialize, Serialize};
 
 pub mod admin;
+pub mod assembly;
 pub mod chat;
+pub mod graph;
+pub mod guard;
+pub mod leak;
+pub mod preview;
 pub mod validate;
 
 use crate::{
-    ExtractState, db,
+    ExtractState,
+    db::{self, RepositoryError},
+    render,
     response::{ApiResult, MapBoxError},
 };
 
+pub use crate::db::{LevelDifficulty, LevelID};
 
-pub use crate::db::{LevelID, LevelDifficulty};
+#[derive(Deserialize, Debug)]
+pub struct UserInfo {
+    username: String,
+}
 
 #[derive(Serialize, Debug)]
 pub struct Level {
@@ -21,6 +35,8 @@ pub struct Level {
     difficulty: LevelDifficulty,
     is_root: bool,
     next: Vec<LevelID>,
+    /// Playable without solving a preceding level
+    is_open: bool,
 }
 
 #[derive(Serialize, Debug)]
@@ -34,26 +50,207 @@ error_response!(GetLevelsError {
 });
 
 pub async fn get_levels(state: ExtractState) -> ApiResult<Json<GetLevelsResponse>> {
-    let levels: Vec<db::Level> = state
-        .dynamo
-        .scan()
-        .table_name(db::Level::TABLE)
-        .send()
+    let levels = playable_levels(&*state.repository)
         .await
         .box_error()
-        .and_then(|output| from_items(output.items.unwrap_or_default()).box_error())
         .map_err(GetLevelsError::QueryLevels)?;
 
     let levels = levels
         .into_iter()
+        .map(|published| published.level)
+        .sorted_by_key(|level| level.level_id)
         .map(|level| Level {
             id: level.level_id,
             name: level.name,
             difficulty: level.difficulty,
             is_root: level.is_root,
             next: level.next,
+            is_open: level.is_open,
         })
         .collect();
 
     Ok(Json(GetLevelsResponse { levels }))
 }
+
+/// Latest published version of every level outside the trash
+pub(crate) async fn playable_levels(
+    repository: &dyn db::Repository,
+) -> db::RepositoryResult<Vec<db::PublishedLevel>> {
+    let trashed: HashSet<_> = repository
+        .get_levels()
+        .await?
+        .into_iter()
+        .filter(|level| level.deleted_at.is_some())
+        .map(|level| level.level_id)
+        .collect();
+
+    let mut levels = repository.get_published_levels().await?;
+    levels.retain(|published| !trashed.contains(&published.level_id));
+    Ok(levels)
+}
+
+/// Latest published version of a level, fails with [`RepositoryError::NotFound`]
+/// while the level is in the trash
+pub(crate) async fn playable_level(
+    repository: &dyn db::Repository,
+    level_id: LevelID,
+) -> db::RepositoryResult<db::PublishedLevel> {
+    match repository.get_level(level_id).await {
+        Ok(level) if level.deleted_at.is_some() => return Err(RepositoryError::NotFound),
+        Ok(_) | Err(RepositoryError::NotFound) => {}
+        Err(err) => return Err(err),
+    }
+
+    repository.get_published_level(level_id).await
+}
+
+/// Levels a player can play: all roots and open levels, and the levels following the ones they solved
+pub(crate) fn unlocked_levels<'a>(
+    levels: impl IntoIterator<Item = &'a db::Level>,
+    solved: &HashSet<LevelID>,
+) -> BTreeSet<LevelID> {
+    let levels: Vec<_> = levels.into_iter().collect();
+    let existing: HashSet<_> = levels.iter().map(|level| level.level_id).collect();
+
+    levels
+        .iter()
+        .filter(|level| level.is_root || level.is_open)
+        .map(|level| level.level_id)
+        .chain(
+            levels
+                .iter()
+                .filter(|level| solved.contains(&level.level_id))
+                .flat_map(|level| level.next.iter().copied()),
+        )
+        .filter(|level_id| existing.contains(level_id))
+        .collect()
+}
+
+error_response!(LevelAccessError {
+    /// Level is locked, solve a level leading to it first
+    LevelLocked[FORBIDDEN],
+    /// Failed to fetch the player's progress
+    QueryProgress(BoxError),
+    /// Failed to fetch the player's groups
+    QueryGroups(BoxError)
+});
+
+/// Fail with [`LevelAccessError::LevelLocked`] unless the player unlocked the published level
+///
+/// Only verified usernames may be passed, guests can play roots and open levels.
+/// Level managers may play every level.
+pub(crate) async fn ensure_unlocked(
+    state: &crate::State,
+    level: &db::Level,
+    username: Option<&str>,
+) -> ApiResult<()> {
+    if level.is_root || level.is_open {
+        return Ok(());
+    }
+    let Some(username) = username else {
+        return Err(LevelAccessError::LevelLocked.into());
+    };
+
+    let solved: HashSet<_> = state
+        .repository
+        .get_level_solves(username)
+        .await
+        .box_error()
+        .map_err(LevelAccessError::QueryProgress)?
+        .into_iter()
+        .map(|solve| solve.level_id)
+        .collect();
+
+    if !solved.is_empty() {
+        let levels = playable_levels(&*state.repository)
+            .await
+            .box_error()
+            .map_err(LevelAccessError::QueryProgress)?;
+
+        if unlocked_levels(levels.iter().map(|published| &published.level), &solved)
+            .contains(&level.level_id)
+        {
+            return Ok(());
+        }
+    }
+
+    if state
+        .groups
+        .is_in_group(username, crate::auth::LEVEL_MANAGER_GROUP)
+        .await
+        .map_err(LevelAccessError::QueryGroups)?
+    {
+        Ok(())
+    } else {
+        Err(LevelAccessError::LevelLocked.into())
+    }
+}
+
+/// Record the player's first solve of a level, counting the messages they sent on it so far
+///
+/// Failures are only logged, so they never cost the player their reply.
+pub(crate) async fn record_solve(
+    repository: &dyn db::Repository,
+    username: String,
+    level_id: LevelID,
+) {
+    let message_count = match repository.get_chat_sessions(&username).await {
+        Ok(sessions) => sessions
+            .iter()
+            .filter(|session| session.level_id == level_id)
+            .map(|session| session.turn_count)
+            .sum(),
+        Err(err) => {
+            tracing::error!("Failed to count messages for level solve: {err}");
+            0
+        }
+    };
+
+    let solve = db::LevelSolve {
+        username,
+        level_id,
+        solved_at: db::timestamp_now(),
+        message_count,
+    };
+    if let Err(err) = repository.record_level_solve(solve).await {
+        tracing::error!("Failed to record level solve: {err}");
+    }
+}
+
+/// Texts of the level's prompt blocks in prompt order,
+/// together with all components of its template for includes
+pub(crate) async fn level_prompt(
+    repository: &dyn db::Repository,
+    level: &db::Level,
+) -> db::RepositoryResult<(Vec<String>, render::ComponentLibrary)> {
+    if level.prompt_components.is_empty() && level.prompt_layout.is_empty() {
+        return Ok(Default::default());
+    }
+
+    let mut components = repository.get_components(&level.template_id).await?;
+    components.retain(|component| component.deleted_at.is_none());
+    let library = render::component_library(&components);
+
+    let prompt = if level.prompt_layout.is_empty() {
+        components
+            .into_iter()
+            .filter(|component| level.prompt_components.contains(&component.component_id))
+            .map(|component| component.text)
+            .collect()
+    } else {
+        // Components deleted from the template are left out, like in template order
+        level
+            .prompt_layout
+            .iter()
+            .filter_map(|block| match block {
+                db::PromptBlock::Component { component_id } => components
+                    .iter()
+                    .find(|component| component.component_id == *component_id)
+                    .map(|component| component.text.clone()),
+                db::PromptBlock::Text { text } => Some(text.clone()),
+            })
+            .collect()
+    };
+
+    Ok((prompt, library))
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    hash::{BuildHasher, RandomState},
+};
+
+use axum::extract::FromRequest;
+
+use super::{
+    chat::{ChatError, PreparedChat},
+    *,
+};
+use crate::{auth::AuthorizedLevelManager, response::ApiError};
+
+/// Unsaved level to chat with, carrying just what the prompt and model need
+#[derive(Deserialize, Debug)]
+pub struct PreviewDraft {
+    #[serde(default = "default_draft_name")]
+    name: String,
+    password: String,
+    /// Texts of the prompt components in prompt order
+    components: Vec<String>,
+    #[serde(default)]
+    model_settings: db::ModelSettings,
+    #[serde(default)]
+    input_guards: Vec<db::InputGuard>,
+    #[serde(default)]
+    output_guards: Vec<db::OutputGuard>,
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+}
+
+fn default_draft_name() -> String {
+    "Preview".to_owned()
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct PreviewChatRequest {
+    /// Saved draft of a level, not its published version
+    level_id: Option<LevelID>,
+    draft: Option<PreviewDraft>,
+    message: String,
+    /// Reuse to continue a previous preview conversation, a random one is chosen otherwise
+    #[serde(default)]
+    session_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PreviewChatResponse {
+    reply: String,
+    /// System prompt sent to the model
+    instruction: String,
+    model: String,
+    session_id: String,
+    /// How the reply leaked the password, if it did
+    leak: Option<db::PasswordLeak>,
+}
+
+error_response!(PreviewChatError {
+    /// Exactly one of level_id and draft is required
+    AmbiguousTarget[BAD_REQUEST],
+    /// Level does not exist
+    LevelDoesNotExist[NOT_FOUND],
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Failed to render draft prompt: {error}
+    InvalidDraft[BAD_REQUEST] { error: String }
+});
+
+/// Chat with a draft level as the calling level manager
+///
+/// Nothing is recorded, so previews never show up in transcripts or player progress.
+pub async fn admin_preview_chat(
+    manager: AuthorizedLevelManager,
+    state: ExtractState,
+    request: PreviewChatRequest,
+) -> ApiResult<Json<PreviewChatResponse>> {
+    let is_draft = request.draft.is_some();
+    let (level, prompt, library) = match (request.level_id, request.draft) {
+        (Some(level_id), None) => {
+            let level = state
+                .repository
+                .get_level(level_id)
+                .await
+                .map_err(|err| match err {
+                    RepositoryError::NotFound => PreviewChatError::LevelDoesNotExist,
+                    RepositoryError::Backend(err) => PreviewChatError::QueryLevel(err),
+                })?;
+
+            let (prompt, library) = level_prompt(&*state.repository, &level)
+                .await
+                .box_error()
+                .map_err(PreviewChatError::QueryComponents)?;
+
+            (level, prompt, library)
+        }
+        (None, Some(draft)) => {
+            let level = db::Level {
+                level_id: LevelID(0),
+                name: draft.name,
+                password: draft.password,
+                difficulty: LevelDifficulty::Low,
+                template_id: db::TemplateID::default(),
+                prompt_components: Vec::new(),
+                prompt_layout: Vec::new(),
+                is_root: false,
+                next: Vec::new(),
+                is_open: false,
+                model_settings: draft.model_settings,
+                solve_on_leak: false,
+                input_guards: draft.input_guards,
+                output_guards: draft.output_guards,
+                variables: draft.variables,
+                deleted_at: None,
+            };
+
+            (level, draft.components, Default::default())
+        }
+        _ => return Err(PreviewChatError::AmbiguousTarget.into()),
+    };
+
+    let session_id = request.session_id.unwrap_or_else(preview_session_id);
+
+    // Previews share the model's conversation history with real chats, so they get their own ids
+    let chat = PreparedChat::new(
+        level,
+        &prompt,
+        &library,
+        format!("preview:{}", manager.username()),
+        session_id.clone(),
+        manager.username(),
+        request.message,
+    )
+    .map_err(|err| match err {
+        ChatError::RenderPrompt(err) if is_draft => PreviewChatError::InvalidDraft {
+            error: err.to_string(),
+        }
+        .into(),
+        err => Box::<dyn ApiError>::from(err),
+    })?;
+
+    let model = chat.model(&state.models).to_owned();
+    let reply = state
+        .models
+        .complete(&model, chat.completion_request())
+        .await?;
+    let reply =
+        guard::apply_output_guards(&state.models, &chat.level, &chat.session_id, reply).await?;
+
+    Ok(Json(PreviewChatResponse {
+        leak: leak::detect(&chat.level.password, &reply),
+        reply,
+        instruction: chat.instruction,
+        model,
+        session_id,
+    }))
+}
+
+/// Random id, so previews started at the same time never continue each other
+fn preview_session_id() -> String {
+    let nonce = RandomState::new().hash_one(db::timestamp_now());
+    format!("preview-{nonce:016x}")
+}
@@ -1,11 +1,12 @@
 use axum::{
     Json,
     extract::{FromRequest, Path},
+    response::Response,
 };
 use serde::Deserialize;
-use serde_dynamo::from_item;
 
 use super::*;
+use crate::auth::AuthorizedPlayer;
 
 #[derive(Deserialize, FromRequest, Debug)]
 #[from_request(via(Json))]
@@ -25,31 +26,29 @@ error_response!(ValidatePasswordError {
     QueryLevel(BoxError)
 });
 
+/// Solves are only recorded for signed in players
 pub async fn validate_password(
     state: ExtractState,
+    player: Result<AuthorizedPlayer, Response>,
     Path(level_id): Path<LevelID>,
     request: ValidatePasswordRequest,
 ) -> ApiResult<Json<ValidatePasswordResponse>> {
-    let level = state
-        .dynamo
-        .get_item()
-        .table_name(db::Level::TABLE)
-        .key(
-            db::Level::PARTITION,
-            aws_sdk_dynamodb::types::AttributeValue::N(level_id.0.to_string()),
-        )
-        .send()
+    let level = playable_level(&*state.repository, level_id)
         .await
-        .box_error()
-        .map_err(ValidatePasswordError::QueryLevel)?
-        .item
-        .ok_or(ValidatePasswordError::DoesNotExist)?;
+        .map(|published| published.level)
+        .map_err(|err| match err {
+            RepositoryError::NotFound => ValidatePasswordError::DoesNotExist,
+            RepositoryError::Backend(err) => ValidatePasswordError::QueryLevel(err),
+        })?;
 
-    let level: db::Level = from_item(level)
-        .box_error()
-        .map_err(ValidatePasswordError::QueryLevel)?;
+    let username = player.as_ref().ok().map(AuthorizedPlayer::username);
+    ensure_unlocked(&state, &level, username).await?;
 
     let is_correct = level.password.trim() == request.password.trim();
 
+    if is_correct && let Ok(player) = player {
+        record_solve(&*state.repository, player.username().to_owned(), level_id).await;
+    }
+
     Ok(Json(ValidatePasswordResponse { is_correct }))
 }
@@ -1,5 +1,8 @@
 mod levels;
+mod progress;
 mod prompt;
+mod sessions;
+mod trash;
 
 api_routes! {
     ["ping"] {
@@ -10,19 +13,82 @@ api_routes! {
         GET --> levels::get_levels;
     }
     ["levels", (level_id), "chat", (session_id)] {
-        POST --> levels::chat::chat_session;
+        GET |-> sessions::get_transcript;
+        POST ?-> levels::chat::chat_session;
+    }
+    ["levels", (level_id), "chat", (session_id), "stream"] {
+        POST ?-> levels::chat::chat_session_stream;
     }
     ["levels", (level_id), "validate"] {
-        POST --> levels::validate::validate_password;
+        POST ?-> levels::validate::validate_password;
+    }
+    ["sessions"] {
+        GET |-> sessions::get_sessions;
+    }
+    ["me", "progress"] {
+        GET |-> progress::get_progress;
     }
     ["admin", "levels"] {
         GET |-> levels::admin::admin_get_levels;
         POST |-> levels::admin::admin_create_level;
     }
+    ["admin", "levels", "graph"] {
+        GET |-> levels::graph::admin_get_level_graph;
+    }
+    ["admin", "levels", "publish"] {
+        POST |-> levels::admin::admin_publish_unpublished_levels;
+    }
     ["admin", "levels", (level_id)] {
         PATCH |-> levels::admin::admin_modify_level;
         DELETE |-> levels::admin::admin_delete_level;
     }
+    ["admin", "levels", (level_id), "prompt"] {
+        GET |-> levels::assembly::admin_get_prompt_assembly;
+    }
+    ["admin", "levels", (level_id), "publish"] {
+        POST |-> levels::admin::admin_publish_level;
+    }
+    ["admin", "preview", "chat"] {
+        POST |-> levels::preview::admin_preview_chat;
+    }
+    ["admin", "trash"] {
+        GET |-> trash::admin_get_trash;
+    }
+    ["admin", "trash", "levels", (level_id)] {
+        DELETE |-> trash::admin_purge_level;
+    }
+    ["admin", "trash", "levels", (level_id), "restore"] {
+        POST |-> trash::admin_restore_level;
+    }
+    ["admin", "trash", "components", (component_id)] {
+        DELETE |-> trash::admin_purge_component;
+    }
+    ["admin", "trash", "components", (component_id), "restore"] {
+        POST |-> trash::admin_restore_component;
+    }
+    ["admin", "transcripts"] {
+        GET |-> sessions::admin::admin_get_transcripts;
+    }
+    ["admin", "transcripts", (session_id)] {
+        GET |-> sessions::admin::admin_get_transcript;
+    }
+    ["admin", "prompt", "templates"] {
+        GET |-> prompt::templates::admin_get_templates;
+        POST |-> prompt::templates::admin_create_template;
+    }
+    ["admin", "prompt", "templates", (template_id)] {
+        PATCH |-> prompt::templates::admin_rename_template;
+        DELETE |-> prompt::templates::admin_delete_template;
+    }
+    ["admin", "prompt", "templates", (template_id), "clone"] {
+        POST |-> prompt::templates::admin_clone_template;
+    }
+    ["admin", "prompt", "templates", (template_id), "rebalance"] {
+        POST |-> prompt::templates::admin_rebalance_template;
+    }
+    ["admin", "prompt", "templates", (template_id), "order"] {
+        PUT |-> prompt::templates::admin_reorder_template;
+    }
     ["admin", "prompt", "components"] {
         GET |-> prompt::admin_get_components;
         POST |-> prompt::admin_add_component;
@@ -34,4 +100,13 @@ api_routes! {
     ["admin", "prompt", "components", (component_id), "position"] {
         PUT |-> prompt::admin_move_component;
     }
+    ["admin", "prompt", "components", (component_id), "revisions"] {
+        GET |-> prompt::revisions::admin_get_revisions;
+    }
+    ["admin", "prompt", "components", (component_id), "revisions", "diff"] {
+        GET |-> prompt::revisions::admin_diff_revisions;
+    }
+    ["admin", "prompt", "components", (component_id), "revisions", (revision), "rollback"] {
+        POST |-> prompt::revisions::admin_rollback_component;
+    }
 }
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use axum::{BoxError, Json};
+use serde::Serialize;
+
+use super::levels::{playable_levels, unlocked_levels};
+use crate::{
+    ExtractState,
+    auth::AuthorizedPlayer,
+    db::LevelID,
+    response::{ApiResult, MapBoxError},
+};
+
+#[derive(Serialize, Debug)]
+pub struct SolvedLevel {
+    level_id: LevelID,
+    solved_at: u64,
+    /// Messages the player sent on the level up to the solve
+    message_count: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetProgressResponse {
+    /// Solves of the player, ordered by level
+    solved: Vec<SolvedLevel>,
+    /// Published levels the player can play, in ascending order
+    unlocked: Vec<LevelID>,
+}
+
+error_response!(GetProgressError {
+    /// Failed to fetch solved levels
+    QuerySolves(BoxError),
+    /// Failed to fetch levels
+    QueryLevels(BoxError)
+});
+
+pub async fn get_progress(
+    player: AuthorizedPlayer,
+    state: ExtractState,
+) -> ApiResult<Json<GetProgressResponse>> {
+    let solves = state
+        .repository
+        .get_level_solves(player.username())
+        .await
+        .box_error()
+        .map_err(GetProgressError::QuerySolves)?;
+
+    let levels = playable_levels(&*state.repository)
+        .await
+        .box_error()
+        .map_err(GetProgressError::QueryLevels)?;
+
+    let solved: HashSet<_> = solves.iter().map(|solve| solve.level_id).collect();
+    let unlocked = unlocked_levels(levels.iter().map(|published| &published.level), &solved)
+        .into_iter()
+        .collect();
+
+    let solved = solves
+        .into_iter()
+        .map(|solve| SolvedLevel {
+            level_id: solve.level_id,
+            solved_at: solve.solved_at,
+            message_count: solve.message_count,
+        })
+        .collect();
+
+    Ok(Json(GetProgressResponse { solved, unlocked }))
+}
@@ -0,0 +1,365 @@
+use axum::{
+    BoxError, Json,
+    extract::{FromRequest, Path, Query},
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub mod revisions;
+pub mod templates;
+
+use crate::{
+    ExtractState,
+    auth::AuthorizedLevelManager,
+    db::{self, RepositoryError},
+    render,
+    response::{ApiResult, MapBoxError},
+};
+
+pub use db::{ComponentID, TemplateID};
+
+/// The default template exists even if it was never stored
+pub(crate) async fn template_exists(
+    repository: &dyn db::Repository,
+    template_id: &TemplateID,
+) -> db::RepositoryResult<bool> {
+    if template_id.is_default() {
+        return Ok(true);
+    }
+
+    match repository.get_template(template_id).await {
+        Ok(_) => Ok(true),
+        Err(RepositoryError::NotFound) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TemplateQuery {
+    /// Defaults to the default template
+    template_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Component {
+    id: ComponentID,
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetComponentsResponse {
+    /// Ordered list of components
+    components: Vec<Component>,
+}
+
+error_response!(GetComponentsError {
+    /// Template does not exist
+    TemplateDoesNotExist[NOT_FOUND],
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError)
+});
+
+pub async fn admin_get_components(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Query(query): Query<TemplateQuery>,
+) -> ApiResult<Json<GetComponentsResponse>> {
+    let template_id = query.template_id.map(TemplateID).unwrap_or_default();
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(GetComponentsError::QueryTemplate)?
+    {
+        return Err(GetComponentsError::TemplateDoesNotExist.into());
+    }
+
+    let components = state
+        .repository
+        .get_components(&template_id)
+        .await
+        .box_error()
+        .map_err(GetComponentsError::QueryComponents)?;
+
+    let components = components
+        .into_iter()
+        .filter(|component| component.deleted_at.is_none())
+        .map(|component| Component {
+            id: component.component_id,
+            text: component.text,
+        })
+        .collect();
+
+    Ok(Json(GetComponentsResponse { components }))
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct AddComponentRequest {
+    /// Defaults to the default template
+    #[serde(default)]
+    template_id: Option<TemplateID>,
+    predecessor: Option<ComponentID>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AddComponentResponse {
+    component_id: ComponentID,
+}
+
+error_response!(AddComponentError {
+    /// Template does not exist
+    TemplateDoesNotExist[NOT_FOUND],
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Predecessor does not exist
+    PredecessorDoesNotExist[NOT_FOUND],
+    /// Failed to fetch adjacent prompt component
+    QueryAdjacent(BoxError),
+    /// Unable to create prompt component
+    ComponentCreation(BoxError)
+});
+
+pub async fn admin_add_component(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    request: AddComponentRequest,
+) -> ApiResult<Json<AddComponentResponse>> {
+    let template_id = request.template_id.unwrap_or_default();
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(AddComponentError::QueryTemplate)?
+    {
+        return Err(AddComponentError::TemplateDoesNotExist.into());
+    }
+
+    let component_id = db::ComponentID(
+        db::Counter::increment(&*state.repository, db::Counter::PROMPT_COMPONENT_ID).await?,
+    );
+    let ordering =
+        db::PromptComponent::create_sort_key(&*state.repository, &template_id, request.predecessor)
+            .await
+            .box_error()
+            .map_err(AddComponentError::QueryAdjacent)?
+            .ok_or(AddComponentError::PredecessorDoesNotExist)?;
+
+    let component = db::PromptComponent {
+        component_id: component_id,
+        template_id,
+        ordering,
+        text: String::default(),
+        deleted_at: None,
+    };
+
+    state
+        .repository
+        .put_component(&component)
+        .await
+        .box_error()
+        .map_err(AddComponentError::ComponentCreation)?;
+
+    Ok(Json(AddComponentResponse { component_id }))
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct ModifyComponentRequest {
+    new_text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ModifyComponentResponse {
+    /// Revision created by the edit
+    revision: u64,
+}
+
+error_response!(ModifyComponentError {
+    /// Component does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Invalid template syntax: {error}
+    InvalidTemplate[BAD_REQUEST] { error: String },
+    /// Failed to update component
+    UpdateComponent(BoxError)
+});
+
+pub async fn admin_modify_component(
+    manager: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+    request: ModifyComponentRequest,
+) -> ApiResult<Json<ModifyComponentResponse>> {
+    render::validate(&request.new_text).map_err(|err| ModifyComponentError::InvalidTemplate {
+        error: format!("{err:#}"),
+    })?;
+
+    let revision = state
+        .repository
+        .revise_component_text(db::NewComponentRevision {
+            component_id,
+            author: manager.username().to_owned(),
+            timestamp: db::timestamp_now(),
+            text: request.new_text,
+            restored_from: None,
+        })
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => ModifyComponentError::DoesNotExist,
+            RepositoryError::Backend(err) => ModifyComponentError::UpdateComponent(err),
+        })?;
+
+    Ok(Json(ModifyComponentResponse {
+        revision: revision.revision,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeleteComponentQuery {
+    /// Remove the component from the prompts of levels instead of refusing to delete it
+    #[serde(default)]
+    cascade: bool,
+}
+
+error_response!(DeleteComponentError {
+    /// Component does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Component is still used by levels {levels}
+    ComponentInUse[CONFLICT] { levels: String },
+    /// Component is used by more than {maximum} levels, too many to update at once
+    TooManyReferences[CONFLICT] { maximum: usize },
+    /// Failed to fetch levels
+    QueryLevels(BoxError),
+    /// Unable to delete prompt component
+    ComponentDeletion(BoxError)
+});
+
+/// Move the component to the trash
+pub async fn admin_delete_component(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+    Query(query): Query<DeleteComponentQuery>,
+) -> ApiResult<()> {
+    let is_component = |block: &db::PromptBlock| match block {
+        db::PromptBlock::Component { component_id: id } => *id == component_id,
+        db::PromptBlock::Text { .. } => false,
+    };
+
+    let references: Vec<_> = state
+        .repository
+        .get_levels()
+        .await
+        .box_error()
+        .map_err(DeleteComponentError::QueryLevels)?
+        .into_iter()
+        .filter(|level| level.deleted_at.is_none())
+        .filter(|level| {
+            level.prompt_components.contains(&component_id)
+                || level.prompt_layout.iter().any(is_component)
+        })
+        .collect();
+
+    if !query.cascade && !references.is_empty() {
+        let levels = references.iter().map(|level| level.level_id.0).join(", ");
+        return Err(DeleteComponentError::ComponentInUse { levels }.into());
+    }
+
+    // The component itself is changed in the same transaction
+    let maximum = db::MAX_TRANSACTION_ITEMS - 1;
+    if references.len() > maximum {
+        return Err(DeleteComponentError::TooManyReferences { maximum }.into());
+    }
+
+    let updates = references
+        .into_iter()
+        .map(|level| {
+            let update = db::LevelUpdate {
+                prompt_components: Some(
+                    level
+                        .prompt_components
+                        .into_iter()
+                        .filter(|id| *id != component_id)
+                        .collect(),
+                ),
+                prompt_layout: Some(
+                    level
+                        .prompt_layout
+                        .into_iter()
+                        .filter(|block| !is_component(block))
+                        .collect(),
+                ),
+                ..Default::default()
+            };
+            (level.level_id, update)
+        })
+        .collect();
+
+    state
+        .repository
+        .trash_component(component_id, db::timestamp_now(), updates)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => DeleteComponentError::DoesNotExist,
+            RepositoryError::Backend(err) => DeleteComponentError::ComponentDeletion(err),
+        })?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct MoveComponentRequest {
+    predecessor: Option<ComponentID>,
+}
+
+error_response!(MoveComponentError {
+    // Prompt component does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Predecessor does not exist
+    PredecessorDoesNotExist[NOT_FOUND],
+    /// Failed to fetch prompt component
+    QueryComponent(BoxError),
+    /// Failed to fetch adjacent prompt component
+    QueryAdjacent(BoxError),
+    /// Failed to update component position
+    UpdatePosition(BoxError)
+});
+
+pub async fn admin_move_component(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+    request: MoveComponentRequest,
+) -> ApiResult<()> {
+    let component =
+        state
+            .repository
+            .get_component(component_id)
+            .await
+            .map_err(|err| match err {
+                RepositoryError::NotFound => MoveComponentError::DoesNotExist,
+                RepositoryError::Backend(err) => MoveComponentError::QueryComponent(err),
+            })?;
+
+    let ordering = db::PromptComponent::create_sort_key(
+        &*state.repository,
+        &component.template_id,
+        request.predecessor,
+    )
+    .await
+    .box_error()
+    .map_err(MoveComponentError::QueryAdjacent)?
+    .ok_or(MoveComponentError::PredecessorDoesNotExist)?;
+
+    state
+        .repository
+        .update_component_ordering(component_id, ordering)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => MoveComponentError::DoesNotExist,
+            RepositoryError::Backend(err) => MoveComponentError::UpdatePosition(err),
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use similar::{ChangeTag, TextDiff};
+
+use super::*;
+
+#[derive(Serialize, Debug)]
+pub struct GetRevisionsResponse {
+    /// Revisions of the component, oldest first
+    revisions: Vec<db::ComponentRevision>,
+}
+
+error_response!(GetRevisionsError {
+    /// Failed to fetch component revisions
+    QueryRevisions(BoxError)
+});
+
+pub async fn admin_get_revisions(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+) -> ApiResult<Json<GetRevisionsResponse>> {
+    let revisions = state
+        .repository
+        .get_component_revisions(component_id)
+        .await
+        .box_error()
+        .map_err(GetRevisionsError::QueryRevisions)?;
+
+    Ok(Json(GetRevisionsResponse { revisions }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DiffQuery {
+    from: u64,
+    to: u64,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffChangeKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DiffChange {
+    kind: DiffChangeKind,
+    text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DiffRevisionsResponse {
+    /// Word level changes turning the `from` revision into the `to` revision
+    changes: Vec<DiffChange>,
+    /// Line based unified diff of the two revisions
+    unified: String,
+}
+
+error_response!(DiffRevisionsError {
+    /// Revision {revision} does not exist
+    RevisionDoesNotExist[NOT_FOUND] { revision: u64 },
+    /// Failed to fetch component revisions
+    QueryRevisions(BoxError)
+});
+
+pub async fn admin_diff_revisions(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+    Query(DiffQuery { from, to }): Query<DiffQuery>,
+) -> ApiResult<Json<DiffRevisionsResponse>> {
+    let revisions = state
+        .repository
+        .get_component_revisions(component_id)
+        .await
+        .box_error()
+        .map_err(DiffRevisionsError::QueryRevisions)?;
+
+    let text_of = |revision: u64| {
+        revisions
+            .iter()
+            .find(|candidate| candidate.revision == revision)
+            .map(|revision| revision.text.as_str())
+            .ok_or(DiffRevisionsError::RevisionDoesNotExist { revision })
+    };
+    let (old, new) = (text_of(from)?, text_of(to)?);
+
+    let mut changes: Vec<DiffChange> = Vec::new();
+    for change in TextDiff::from_words(old, new).iter_all_changes() {
+        let kind = match change.tag() {
+            ChangeTag::Equal => DiffChangeKind::Equal,
+            ChangeTag::Insert => DiffChangeKind::Insert,
+            ChangeTag::Delete => DiffChangeKind::Delete,
+        };
+
+        match changes.last_mut() {
+            Some(last) if last.kind == kind => {
+                last.text.push_str(change.value());
+            }
+            _ => changes.push(DiffChange {
+                kind,
+                text: change.value().to_owned(),
+            }),
+        }
+    }
+
+    let unified = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("revision {from}"), &format!("revision {to}"))
+        .to_string();
+
+    Ok(Json(DiffRevisionsResponse { changes, unified }))
+}
+
+error_response!(RollbackComponentError {
+    /// Component or revision does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to fetch component revisions
+    QueryRevisions(BoxError),
+    /// Failed to update component
+    UpdateComponent(BoxError)
+});
+
+/// Restore the text of an earlier revision
+///
+/// The history is append-only, so the restored text is saved as a new revision.
+pub async fn admin_rollback_component(
+    manager: AuthorizedLevelManager,
+    state: ExtractState,
+    Path((component_id, revision)): Path<(ComponentID, u64)>,
+) -> ApiResult<Json<ModifyComponentResponse>> {
+    let restored = state
+        .repository
+        .get_component_revisions(component_id)
+        .await
+        .box_error()
+        .map_err(RollbackComponentError::QueryRevisions)?
+        .into_iter()
+        .find(|candidate| candidate.revision == revision)
+        .ok_or(RollbackComponentError::DoesNotExist)?;
+
+    let revision = state
+        .repository
+        .revise_component_text(db::NewComponentRevision {
+            component_id,
+            author: manager.username().to_owned(),
+            timestamp: db::timestamp_now(),
+            text: restored.text,
+            restored_from: Some(restored.revision),
+        })
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => RollbackComponentError::DoesNotExist,
+            RepositoryError::Backend(err) => RollbackComponentError::UpdateComponent(err),
+        })?;
+
+    Ok(Json(ModifyComponentResponse {
+        revision: revision.revision,
+    }))
+}
@@ -0,0 +1,436 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+
+use super::*;
+
+#[derive(Serialize, Debug)]
+pub struct GetTemplatesResponse {
+    /// Templates in order of creation
+    templates: Vec<db::PromptTemplate>,
+}
+
+error_response!(GetTemplatesError {
+    /// Failed to fetch templates
+    QueryTemplates(BoxError)
+});
+
+pub async fn admin_get_templates(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+) -> ApiResult<Json<GetTemplatesResponse>> {
+    let mut templates = state
+        .repository
+        .get_templates()
+        .await
+        .box_error()
+        .map_err(GetTemplatesError::QueryTemplates)?;
+
+    if !templates
+        .iter()
+        .any(|template| template.template_id.is_default())
+    {
+        templates.push(db::PromptTemplate::default_template());
+    }
+
+    let templates = templates
+        .into_iter()
+        .sorted_by_key(|template| template.created_at)
+        .collect();
+
+    Ok(Json(GetTemplatesResponse { templates }))
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct CreateTemplateRequest {
+    name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CreateTemplateResponse {
+    template: db::PromptTemplate,
+}
+
+error_response!(CreateTemplateError {
+    /// Failed to create template
+    TemplateCreation(BoxError)
+});
+
+pub async fn admin_create_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    request: CreateTemplateRequest,
+) -> ApiResult<Json<CreateTemplateResponse>> {
+    let template = db::PromptTemplate {
+        template_id: TemplateID::from_counter(
+            db::Counter::increment(&*state.repository, db::Counter::TEMPLATE_ID).await?,
+        ),
+        name: request.name,
+        created_at: db::timestamp_now(),
+    };
+
+    state
+        .repository
+        .put_template(&template)
+        .await
+        .box_error()
+        .map_err(CreateTemplateError::TemplateCreation)?;
+
+    Ok(Json(CreateTemplateResponse { template }))
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct RenameTemplateRequest {
+    name: String,
+}
+
+error_response!(RenameTemplateError {
+    /// Template does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to rename template
+    TemplateModification(BoxError)
+});
+
+pub async fn admin_rename_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(template_id): Path<TemplateID>,
+    request: RenameTemplateRequest,
+) -> ApiResult<()> {
+    let result = state
+        .repository
+        .update_template_name(&template_id, request.name.clone())
+        .await;
+
+    let result = match result {
+        Err(RepositoryError::NotFound) if template_id.is_default() => {
+            let template = db::PromptTemplate {
+                name: request.name,
+                ..db::PromptTemplate::default_template()
+            };
+            state.repository.put_template(&template).await
+        }
+        result => result,
+    };
+
+    result.map_err(|err| match err {
+        RepositoryError::NotFound => RenameTemplateError::DoesNotExist,
+        RepositoryError::Backend(err) => RenameTemplateError::TemplateModification(err),
+    })?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct CloneTemplateRequest {
+    name: String,
+}
+
+error_response!(CloneTemplateError {
+    /// Template does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Failed to create template
+    TemplateCreation(BoxError)
+});
+
+/// Copy a template together with all of its components
+///
+/// The copies get new component ids, so levels keep using the components of the original.
+pub async fn admin_clone_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(template_id): Path<TemplateID>,
+    request: CloneTemplateRequest,
+) -> ApiResult<Json<CreateTemplateResponse>> {
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(CloneTemplateError::QueryTemplate)?
+    {
+        return Err(CloneTemplateError::DoesNotExist.into());
+    }
+
+    let components = state
+        .repository
+        .get_components(&template_id)
+        .await
+        .box_error()
+        .map_err(CloneTemplateError::QueryComponents)?;
+
+    let template = db::PromptTemplate {
+        template_id: TemplateID::from_counter(
+            db::Counter::increment(&*state.repository, db::Counter::TEMPLATE_ID).await?,
+        ),
+        name: request.name,
+        created_at: db::timestamp_now(),
+    };
+
+    state
+        .repository
+        .put_template(&template)
+        .await
+        .box_error()
+        .map_err(CloneTemplateError::TemplateCreation)?;
+
+    let components = components
+        .into_iter()
+        .filter(|component| component.deleted_at.is_none());
+    for component in components {
+        let component = db::PromptComponent {
+            component_id: db::ComponentID(
+                db::Counter::increment(&*state.repository, db::Counter::PROMPT_COMPONENT_ID)
+                    .await?,
+            ),
+            template_id: template.template_id.clone(),
+            ..component
+        };
+
+        state
+            .repository
+            .put_component(&component)
+            .await
+            .box_error()
+            .map_err(CloneTemplateError::TemplateCreation)?;
+    }
+
+    Ok(Json(CreateTemplateResponse { template }))
+}
+
+error_response!(RebalanceTemplateError {
+    /// Template does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Templates with more than {maximum} components cannot be rebalanced
+    TooManyComponents[CONFLICT] { maximum: usize },
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to rewrite component ordering
+    RewriteOrdering(BoxError)
+});
+
+/// Replace the ordering keys of the template's components with short ones, keeping their order
+pub async fn admin_rebalance_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(template_id): Path<TemplateID>,
+) -> ApiResult<()> {
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(RebalanceTemplateError::QueryTemplate)?
+    {
+        return Err(RebalanceTemplateError::DoesNotExist.into());
+    }
+
+    db::PromptComponent::rebalance(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(RebalanceTemplateError::RewriteOrdering)?
+        .ok_or(RebalanceTemplateError::TooManyComponents {
+            maximum: db::MAX_TRANSACTION_ITEMS,
+        })?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, FromRequest, Debug)]
+#[from_request(via(Json))]
+pub struct ReorderTemplateRequest {
+    /// Ids of all components of the template, except the ones in the trash, in their new order
+    components: Vec<ComponentID>,
+}
+
+error_response!(ReorderTemplateError {
+    /// Template does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Submitted components do not match the components of the template
+    ComponentMismatch[CONFLICT],
+    /// At most {maximum} components can be reordered at once
+    TooManyComponents[BAD_REQUEST] { maximum: usize },
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Failed to rewrite component ordering
+    RewriteOrdering(BoxError)
+});
+
+/// Put all components of a template into the given order in a single transaction
+///
+/// Components in the trash stay behind the component they followed before,
+/// so restoring them puts them back at their previous position.
+pub async fn admin_reorder_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(template_id): Path<TemplateID>,
+    request: ReorderTemplateRequest,
+) -> ApiResult<()> {
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(ReorderTemplateError::QueryTemplate)?
+    {
+        return Err(ReorderTemplateError::DoesNotExist.into());
+    }
+
+    let components = state
+        .repository
+        .get_components(&template_id)
+        .await
+        .box_error()
+        .map_err(ReorderTemplateError::QueryComponents)?;
+    if components.len() > db::MAX_TRANSACTION_ITEMS {
+        return Err(ReorderTemplateError::TooManyComponents {
+            maximum: db::MAX_TRANSACTION_ITEMS,
+        }
+        .into());
+    }
+
+    let stored: HashSet<_> = components
+        .iter()
+        .filter(|component| component.deleted_at.is_none())
+        .map(|component| component.component_id)
+        .collect();
+    let submitted: HashSet<_> = request.components.iter().copied().collect();
+    if submitted.len() != request.components.len() || submitted != stored {
+        return Err(ReorderTemplateError::ComponentMismatch.into());
+    }
+
+    let mut trashed_after: HashMap<Option<ComponentID>, Vec<ComponentID>> = HashMap::new();
+    let mut predecessor = None;
+    for component in &components {
+        if component.deleted_at.is_some() {
+            trashed_after
+                .entry(predecessor)
+                .or_default()
+                .push(component.component_id);
+        } else {
+            predecessor = Some(component.component_id);
+        }
+    }
+
+    let leading = trashed_after.remove(&None).unwrap_or_default();
+    let order = leading
+        .into_iter()
+        .chain(request.components.into_iter().flat_map(|component_id| {
+            let trashed = trashed_after
+                .remove(&Some(component_id))
+                .unwrap_or_default();
+            std::iter::once(component_id).chain(trashed)
+        }))
+        .collect_vec();
+    let orderings = order
+        .into_iter()
+        .zip(db::PromptComponent::create_balanced_sort_keys(
+            components.len(),
+        ))
+        .collect();
+
+    state
+        .repository
+        .update_component_orderings(&template_id, orderings)
+        .await
+        .map_err(|err| match err {
+            // A component was deleted or moved to another template in the meantime
+            RepositoryError::NotFound => ReorderTemplateError::ComponentMismatch,
+            RepositoryError::Backend(err) => ReorderTemplateError::RewriteOrdering(err),
+        })?;
+
+    Ok(())
+}
+
+error_response!(DeleteTemplateError {
+    /// Template does not exist
+    DoesNotExist[NOT_FOUND],
+    /// The default template cannot be deleted
+    DefaultTemplate[BAD_REQUEST],
+    /// Template is still used by levels {levels}
+    TemplateInUse[CONFLICT] { levels: String },
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to fetch levels
+    QueryLevels(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError),
+    /// Unable to move prompt components to the trash
+    ComponentDeletion(BoxError),
+    /// Unable to delete template
+    TemplateDeletion(BoxError)
+});
+
+/// Delete a template, as long as no level uses it, and move its components to the trash
+///
+/// Components are trashed before the template is deleted, so a failed request
+/// loses nothing and can simply be repeated.
+pub async fn admin_delete_template(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(template_id): Path<TemplateID>,
+) -> ApiResult<()> {
+    if template_id.is_default() {
+        return Err(DeleteTemplateError::DefaultTemplate.into());
+    }
+
+    if !template_exists(&*state.repository, &template_id)
+        .await
+        .box_error()
+        .map_err(DeleteTemplateError::QueryTemplate)?
+    {
+        return Err(DeleteTemplateError::DoesNotExist.into());
+    }
+
+    let users = state
+        .repository
+        .get_levels()
+        .await
+        .box_error()
+        .map_err(DeleteTemplateError::QueryLevels)?
+        .into_iter()
+        .filter(|level| level.template_id == template_id)
+        .map(|level| level.level_id.0)
+        .join(", ");
+    if !users.is_empty() {
+        return Err(DeleteTemplateError::TemplateInUse { levels: users }.into());
+    }
+
+    let components = state
+        .repository
+        .get_components(&template_id)
+        .await
+        .box_error()
+        .map_err(DeleteTemplateError::QueryComponents)?;
+
+    let deleted_at = db::timestamp_now();
+    for component in components {
+        if component.deleted_at.is_some() {
+            continue;
+        }
+
+        let result = state
+            .repository
+            .trash_component(component.component_id, deleted_at, Vec::new())
+            .await;
+        match result {
+            // Purged in the meantime
+            Ok(()) | Err(RepositoryError::NotFound) => {}
+            Err(RepositoryError::Backend(err)) => {
+                return Err(DeleteTemplateError::ComponentDeletion(err).into());
+            }
+        }
+    }
+
+    state
+        .repository
+        .delete_template(&template_id)
+        .await
+        .box_error()
+        .map_err(DeleteTemplateError::TemplateDeletion)?;
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use axum::extract::Query;
+use futures::future::try_join_all;
+use itertools::Itertools;
+use serde::Deserialize;
+
+use super::*;
+use crate::auth::AuthorizedLevelManager;
+
+/// Sessions filtered per request unless filtering by username, further ones are paged
+const SESSIONS_PER_PAGE: usize = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct TranscriptFilter {
+    level_id: Option<u64>,
+    username: Option<String>,
+    /// Only sessions active at or after this unix timestamp
+    from: Option<u64>,
+    /// Only sessions started at or before this unix timestamp
+    to: Option<u64>,
+    /// Only sessions whose player did (or did not) validate the level password afterwards
+    ///
+    /// Solves are only recorded for signed in players, so sessions of guests are never solved.
+    solved: Option<bool>,
+    /// Whitespace separated terms which must all occur in a single message or reply
+    search: Option<String>,
+    /// Continue with the page of sessions following the one whose response returned this `next`
+    after: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminSession {
+    session_id: String,
+    client_session_id: String,
+    level_id: LevelID,
+    username: String,
+    started_at: u64,
+    updated_at: u64,
+    turn_count: u64,
+    first_leak_at: Option<u64>,
+    leak_count: u64,
+    /// Whether the player validated the level password after starting this session,
+    /// always false for guests
+    solved: bool,
+    /// Turns matching the search terms, omitted if no search was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matching_turns: Option<Vec<u64>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminGetTranscriptsResponse {
+    /// Matching sessions of this page, most recently active first
+    sessions: Vec<AdminSession>,
+    /// Pass as `after` to filter the next page, absent once all sessions were filtered
+    ///
+    /// Filtering by username returns all sessions of the player at once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+}
+
+error_response!(AdminGetTranscriptsError {
+    /// Failed to fetch chat sessions
+    QuerySessions(BoxError),
+    /// Failed to fetch level solves
+    QuerySolves(BoxError),
+    /// Failed to search transcripts
+    SearchTurns(BoxError)
+});
+
+pub async fn admin_get_transcripts(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Query(filter): Query<TranscriptFilter>,
+) -> ApiResult<Json<AdminGetTranscriptsResponse>> {
+    let (sessions, next) = match &filter.username {
+        Some(username) => {
+            let sessions = state
+                .repository
+                .get_chat_sessions(username)
+                .await
+                .box_error()
+                .map_err(AdminGetTranscriptsError::QuerySessions)?;
+            (sessions, None)
+        }
+        None => {
+            let page = state
+                .repository
+                .scan_chat_sessions(filter.after.as_deref(), SESSIONS_PER_PAGE)
+                .await
+                .box_error()
+                .map_err(AdminGetTranscriptsError::QuerySessions)?;
+            (page.sessions, page.next)
+        }
+    };
+
+    let sessions = sessions
+        .into_iter()
+        .filter(|session| {
+            filter
+                .level_id
+                .is_none_or(|level_id| session.level_id == LevelID(level_id))
+                && filter.from.is_none_or(|from| session.updated_at >= from)
+                && filter.to.is_none_or(|to| session.started_at <= to)
+        })
+        .collect_vec();
+
+    let solves: HashMap<(String, LevelID), u64> = try_join_all(
+        sessions
+            .iter()
+            .map(|session| session.username.as_str())
+            .unique()
+            .map(|username| state.repository.get_level_solves(username)),
+    )
+    .await
+    .box_error()
+    .map_err(AdminGetTranscriptsError::QuerySolves)?
+    .into_iter()
+    .flatten()
+    .map(|solve| ((solve.username, solve.level_id), solve.solved_at))
+    .collect();
+
+    let sessions = sessions
+        .into_iter()
+        .filter_map(|session| {
+            let solved = solves
+                .get(&(session.username.clone(), session.level_id))
+                .is_some_and(|&solved_at| solved_at >= session.started_at);
+            filter
+                .solved
+                .is_none_or(|wanted| wanted == solved)
+                .then_some((session, solved))
+        })
+        .collect_vec();
+
+    let search_terms = filter
+        .search
+        .as_deref()
+        .map(|search| {
+            search
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect_vec()
+        })
+        .filter(|terms| !terms.is_empty());
+
+    let matching_turns = match &search_terms {
+        Some(terms) => try_join_all(
+            sessions
+                .iter()
+                .map(|(session, _)| state.repository.get_chat_turns(&session.session_id)),
+        )
+        .await
+        .box_error()
+        .map_err(AdminGetTranscriptsError::SearchTurns)?
+        .into_iter()
+        .map(|turns| {
+            let matching = turns
+                .into_iter()
+                .filter(|turn| {
+                    let content = format!("{}\n{}", turn.message, turn.reply).to_lowercase();
+                    terms.iter().all(|term| content.contains(term.as_str()))
+                })
+                .map(|turn| turn.turn)
+                .collect_vec();
+            Some(matching)
+        })
+        .collect_vec(),
+        None => vec![None; sessions.len()],
+    };
+
+    let sessions = sessions
+        .into_iter()
+        .zip(matching_turns)
+        .filter(|(_, turns)| turns.as_ref().is_none_or(|turns| !turns.is_empty()))
+        .map(|((session, solved), turns)| AdminSession {
+            session_id: session.session_id,
+            client_session_id: session.client_session_id,
+            level_id: session.level_id,
+            username: session.username,
+            started_at: session.started_at,
+            updated_at: session.updated_at,
+            turn_count: session.turn_count,
+            first_leak_at: session.first_leak_at,
+            leak_count: session.leak_count,
+            solved,
+            matching_turns: turns,
+        })
+        .sorted_by(|a, b| b.updated_at.cmp(&a.updated_at))
+        .collect();
+
+    Ok(Json(AdminGetTranscriptsResponse { sessions, next }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdminGetTranscriptResponse {
+    session: AdminSession,
+    /// Turns of the session in chronological order
+    turns: Vec<Turn>,
+}
+
+error_response!(AdminGetTranscriptError {
+    /// Session does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to fetch chat session
+    QuerySession(BoxError),
+    /// Failed to fetch level solves
+    QuerySolves(BoxError),
+    /// Failed to fetch transcript
+    QueryTurns(BoxError)
+});
+
+pub async fn admin_get_transcript(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<AdminGetTranscriptResponse>> {
+    let session = state
+        .repository
+        .get_chat_session(&session_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => AdminGetTranscriptError::DoesNotExist,
+            RepositoryError::Backend(err) => AdminGetTranscriptError::QuerySession(err),
+        })?;
+
+    let solved = state
+        .repository
+        .get_level_solves(&session.username)
+        .await
+        .box_error()
+        .map_err(AdminGetTranscriptError::QuerySolves)?
+        .into_iter()
+        .any(|solve| solve.level_id == session.level_id && solve.solved_at >= session.started_at);
+
+    let turns = state
+        .repository
+        .get_chat_turns(&session_id)
+        .await
+        .box_error()
+        .map_err(AdminGetTranscriptError::QueryTurns)?
+        .into_iter()
+        .map(|turn| Turn {
+            message: turn.message,
+            reply: turn.reply,
+            timestamp: turn.timestamp,
+            leak: turn.leak,
+        })
+        .collect();
+
+    Ok(Json(AdminGetTranscriptResponse {
+        session: AdminSession {
+            session_id: session.session_id,
+            client_session_id: session.client_session_id,
+            level_id: session.level_id,
+            username: session.username,
+            started_at: session.started_at,
+            updated_at: session.updated_at,
+            turn_count: session.turn_count,
+            first_leak_at: session.first_leak_at,
+            leak_count: session.leak_count,
+            solved,
+            matching_turns: None,
+        },
+        turns,
+    }))
+}
@@ -0,0 +1,117 @@
+use axum::{BoxError, Json, extract::Path};
+use serde::Serialize;
+
+pub mod admin;
+
+use crate::{
+    ExtractState,
+    auth::AuthorizedPlayer,
+    db::{self, LevelID, RepositoryError},
+    response::{ApiResult, MapBoxError},
+};
+
+#[derive(Serialize, Debug)]
+pub struct Session {
+    /// Session id as chosen by the client when starting the chat
+    session_id: String,
+    level_id: LevelID,
+    started_at: u64,
+    updated_at: u64,
+    turn_count: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetSessionsResponse {
+    /// Sessions of the player, most recently active first
+    sessions: Vec<Session>,
+}
+
+error_response!(GetSessionsError {
+    /// Failed to fetch chat sessions
+    QuerySessions(BoxError)
+});
+
+pub async fn get_sessions(
+    player: AuthorizedPlayer,
+    state: ExtractState,
+) -> ApiResult<Json<GetSessionsResponse>> {
+    let sessions = state
+        .repository
+        .get_chat_sessions(player.username())
+        .await
+        .box_error()
+        .map_err(GetSessionsError::QuerySessions)?
+        .into_iter()
+        .map(|session| Session {
+            session_id: session.client_session_id,
+            level_id: session.level_id,
+            started_at: session.started_at,
+            updated_at: session.updated_at,
+            turn_count: session.turn_count,
+        })
+        .collect();
+
+    Ok(Json(GetSessionsResponse { sessions }))
+}
+
+#[derive(Serialize, Debug)]
+pub struct Turn {
+    message: String,
+    reply: String,
+    timestamp: u64,
+    /// How the reply leaked the level password, if it did
+    leak: Option<db::PasswordLeak>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetTranscriptResponse {
+    /// Turns of the session in chronological order
+    turns: Vec<Turn>,
+}
+
+error_response!(GetTranscriptError {
+    /// Session does not exist
+    DoesNotExist[NOT_FOUND],
+    /// Failed to fetch chat session
+    QuerySession(BoxError),
+    /// Failed to fetch transcript
+    QueryTurns(BoxError)
+});
+
+pub async fn get_transcript(
+    player: AuthorizedPlayer,
+    state: ExtractState,
+    Path((level_id, session_id)): Path<(LevelID, String)>,
+) -> ApiResult<Json<GetTranscriptResponse>> {
+    let session_id = db::ChatSession::id_for(player.username(), level_id, &session_id);
+
+    let session = state
+        .repository
+        .get_chat_session(&session_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => GetTranscriptError::DoesNotExist,
+            RepositoryError::Backend(err) => GetTranscriptError::QuerySession(err),
+        })?;
+
+    if session.level_id != level_id {
+        return Err(GetTranscriptError::DoesNotExist.into());
+    }
+
+    let turns = state
+        .repository
+        .get_chat_turns(&session_id)
+        .await
+        .box_error()
+        .map_err(GetTranscriptError::QueryTurns)?
+        .into_iter()
+        .map(|turn| Turn {
+            message: turn.message,
+            reply: turn.reply,
+            timestamp: turn.timestamp,
+            leak: turn.leak,
+        })
+        .collect();
+
+    Ok(Json(GetTranscriptResponse { turns }))
+}
@@ -0,0 +1,274 @@
+use axum::{BoxError, Json, extract::Path};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    ExtractState,
+    auth::AuthorizedLevelManager,
+    db::{self, ComponentID, LevelID, RepositoryError, TemplateID},
+    response::{ApiResult, MapBoxError},
+    routes::prompt::template_exists,
+};
+
+#[derive(Serialize, Debug)]
+pub struct TrashedLevel {
+    level_id: LevelID,
+    name: String,
+    deleted_at: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TrashedComponent {
+    id: ComponentID,
+    template_id: TemplateID,
+    text: String,
+    deleted_at: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GetTrashResponse {
+    /// Most recently deleted first
+    levels: Vec<TrashedLevel>,
+    /// Most recently deleted first
+    components: Vec<TrashedComponent>,
+}
+
+error_response!(GetTrashError {
+    /// Failed to fetch levels
+    QueryLevels(BoxError),
+    /// Failed to fetch prompt components
+    QueryComponents(BoxError)
+});
+
+pub async fn admin_get_trash(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+) -> ApiResult<Json<GetTrashResponse>> {
+    let levels = state
+        .repository
+        .get_levels()
+        .await
+        .box_error()
+        .map_err(GetTrashError::QueryLevels)?
+        .into_iter()
+        .filter_map(|level| {
+            Some(TrashedLevel {
+                deleted_at: level.deleted_at?,
+                level_id: level.level_id,
+                name: level.name,
+            })
+        })
+        .sorted_by(|a, b| b.deleted_at.cmp(&a.deleted_at))
+        .collect();
+
+    let components = state
+        .repository
+        .get_all_components()
+        .await
+        .box_error()
+        .map_err(GetTrashError::QueryComponents)?
+        .into_iter()
+        .filter_map(|component| {
+            Some(TrashedComponent {
+                deleted_at: component.deleted_at?,
+                id: component.component_id,
+                template_id: component.template_id,
+                text: component.text,
+            })
+        })
+        .sorted_by(|a, b| b.deleted_at.cmp(&a.deleted_at))
+        .collect();
+
+    Ok(Json(GetTrashResponse { levels, components }))
+}
+
+error_response!(RestoreLevelError {
+    /// Level is not in the trash
+    NotInTrash[NOT_FOUND],
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to restore level
+    LevelRestoration(BoxError)
+});
+
+/// Take the level out of the trash, references removed when deleting it are not restored
+pub async fn admin_restore_level(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(level_id): Path<LevelID>,
+) -> ApiResult<()> {
+    let level = state
+        .repository
+        .get_level(level_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => RestoreLevelError::NotInTrash,
+            RepositoryError::Backend(err) => RestoreLevelError::QueryLevel(err),
+        })?;
+    if level.deleted_at.is_none() {
+        return Err(RestoreLevelError::NotInTrash.into());
+    }
+
+    let update = db::LevelUpdate {
+        deleted_at: Some(None),
+        ..Default::default()
+    };
+    state
+        .repository
+        .update_level(level_id, update)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => RestoreLevelError::NotInTrash,
+            RepositoryError::Backend(err) => RestoreLevelError::LevelRestoration(err),
+        })?;
+
+    Ok(())
+}
+
+error_response!(PurgeLevelError {
+    /// Level is not in the trash
+    NotInTrash[NOT_FOUND],
+    /// Failed to fetch level
+    QueryLevel(BoxError),
+    /// Failed to delete level
+    LevelDeletion(BoxError)
+});
+
+/// Permanently delete a level from the trash together with its published versions
+pub async fn admin_purge_level(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(level_id): Path<LevelID>,
+) -> ApiResult<()> {
+    let level = state
+        .repository
+        .get_level(level_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => PurgeLevelError::NotInTrash,
+            RepositoryError::Backend(err) => PurgeLevelError::QueryLevel(err),
+        })?;
+    if level.deleted_at.is_none() {
+        return Err(PurgeLevelError::NotInTrash.into());
+    }
+
+    state
+        .repository
+        .delete_published_level(level_id)
+        .await
+        .box_error()
+        .map_err(PurgeLevelError::LevelDeletion)?;
+
+    state
+        .repository
+        .delete_level(level_id)
+        .await
+        .box_error()
+        .map_err(PurgeLevelError::LevelDeletion)?;
+
+    Ok(())
+}
+
+error_response!(RestoreComponentError {
+    /// Component is not in the trash
+    NotInTrash[NOT_FOUND],
+    /// Template {template_id} of the component was deleted
+    TemplateDeleted[CONFLICT] { template_id: String },
+    /// Failed to fetch prompt component
+    QueryComponent(BoxError),
+    /// Failed to fetch template
+    QueryTemplate(BoxError),
+    /// Failed to restore prompt component
+    ComponentRestoration(BoxError)
+});
+
+/// Take the component out of the trash at its previous position,
+/// levels it was removed from when deleting it do not use it again
+///
+/// Components of deleted templates can only be purged.
+pub async fn admin_restore_component(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+) -> ApiResult<()> {
+    let component =
+        state
+            .repository
+            .get_component(component_id)
+            .await
+            .map_err(|err| match err {
+                RepositoryError::NotFound => RestoreComponentError::NotInTrash,
+                RepositoryError::Backend(err) => RestoreComponentError::QueryComponent(err),
+            })?;
+    if component.deleted_at.is_none() {
+        return Err(RestoreComponentError::NotInTrash.into());
+    }
+
+    if !template_exists(&*state.repository, &component.template_id)
+        .await
+        .box_error()
+        .map_err(RestoreComponentError::QueryTemplate)?
+    {
+        return Err(RestoreComponentError::TemplateDeleted {
+            template_id: component.template_id.0,
+        }
+        .into());
+    }
+
+    state
+        .repository
+        .restore_component(component_id)
+        .await
+        .map_err(|err| match err {
+            RepositoryError::NotFound => RestoreComponentError::NotInTrash,
+            RepositoryError::Backend(err) => RestoreComponentError::ComponentRestoration(err),
+        })?;
+
+    Ok(())
+}
+
+error_response!(PurgeComponentError {
+    /// Component is not in the trash
+    NotInTrash[NOT_FOUND],
+    /// Failed to fetch prompt component
+    QueryComponent(BoxError),
+    /// Failed to delete prompt component
+    ComponentDeletion(BoxError)
+});
+
+/// Permanently delete a component from the trash together with its revisions
+pub async fn admin_purge_component(
+    _: AuthorizedLevelManager,
+    state: ExtractState,
+    Path(component_id): Path<ComponentID>,
+) -> ApiResult<()> {
+    let component =
+        state
+            .repository
+            .get_component(component_id)
+            .await
+            .map_err(|err| match err {
+                RepositoryError::NotFound => PurgeComponentError::NotInTrash,
+                RepositoryError::Backend(err) => PurgeComponentError::QueryComponent(err),
+            })?;
+    if component.deleted_at.is_none() {
+        return Err(PurgeComponentError::NotInTrash.into());
+    }
+
+    // Revisions go first, so a failed purge leaves the component in the trash to retry
+    state
+        .repository
+        .delete_component_revisions(component_id)
+        .await
+        .box_error()
+        .map_err(PurgeComponentError::ComponentDeletion)?;
+
+    state
+        .repository
+        .delete_component(component_id)
+        .await
+        .box_error()
+        .map_err(PurgeComponentError::ComponentDeletion)?;
+
+    Ok(())
+}
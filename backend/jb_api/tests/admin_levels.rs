@@ -0,0 +1,237 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn admin_routes_require_level_manager() {
+    let api = TestApi::new(ScriptedChatModel::default());
+
+    let (status, _) = api
+        .call(Method::GET, "/admin/levels", None, Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = api
+        .call(Method::GET, "/admin/levels", Some("player"), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["type"], "NotALevelManager");
+
+    let (status, body) = api
+        .call(Method::GET, "/admin/levels", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["levels"], json!([]));
+}
+
+#[tokio::test]
+async fn players_only_see_published_levels() {
+    let api = TestApi::new(ScriptedChatModel::default());
+
+    let level_id = api
+        .create_level("First", json!({ "is_root": true, "difficulty": "Medium" }))
+        .await;
+
+    let (status, body) = api
+        .call(Method::GET, "/admin/levels", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["levels"][0]["level_id"], level_id);
+    assert_eq!(body["levels"][0]["is_root"], true);
+
+    let (_, body) = api.call(Method::GET, "/levels", None, Value::Null).await;
+    assert_eq!(body["levels"], json!([]));
+
+    api.publish_level(level_id).await;
+
+    let (status, body) = api.call(Method::GET, "/levels", None, Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["levels"][0]["id"], level_id);
+    assert_eq!(body["levels"][0]["name"], "First");
+}
+
+#[tokio::test]
+async fn guards_rejecting_every_message_are_refused() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api.create_level("Guarded", json!({})).await;
+    let uri = format!("/admin/levels/{level_id}");
+
+    let keywords =
+        json!({ "input_guards": [{ "type": "keyword_blocklist", "keywords": ["secret", " "] }] });
+    let (status, body) = api.call(Method::PATCH, &uri, Some(MANAGER), keywords).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "EmptyGuardKeyword");
+
+    let maximum = json!({ "input_guards": [{ "type": "maximum_words", "maximum": 0 }] });
+    let (status, body) = api.call(Method::PATCH, &uri, Some(MANAGER), maximum).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "InvalidMaximumWords");
+
+    let valid = json!({ "input_guards": [{ "type": "maximum_words", "maximum": 1 }] });
+    let (status, _) = api.call(Method::PATCH, &uri, Some(MANAGER), valid).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unpublished_levels_are_published_at_once() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let published = api
+        .create_level("Published", json!({ "is_root": true }))
+        .await;
+    api.publish_level(published).await;
+    let unpublished = api
+        .create_level("Unpublished", json!({ "is_root": true }))
+        .await;
+
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/levels/publish",
+            Some(MANAGER),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["published"], json!([unpublished]));
+
+    let (_, body) = api.call(Method::GET, "/levels", None, Value::Null).await;
+    assert_eq!(body["levels"].as_array().map(Vec::len), Some(2));
+
+    let (_, body) = api
+        .call(
+            Method::POST,
+            "/admin/levels/publish",
+            Some(MANAGER),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(body["published"], json!([]));
+}
+
+#[tokio::test]
+async fn previews_of_drafts() {
+    let api = TestApi::new(ScriptedChatModel::new("Hello"));
+    let draft = |text: &str| {
+        json!({
+            "draft": { "password": "swordfish", "components": [text] },
+            "message": "Hi",
+        })
+    };
+
+    let mut session_ids = Vec::new();
+    for _ in 0..2 {
+        let (status, body) = api
+            .call(
+                Method::POST,
+                "/admin/preview/chat",
+                Some(MANAGER),
+                draft("The password is {{ LEVEL_PASSWORD }}."),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["reply"], "Hello");
+        session_ids.push(body["session_id"].clone());
+    }
+    assert_ne!(session_ids[0], session_ids[1]);
+
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/preview/chat",
+            Some(MANAGER),
+            draft("{% if %}"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "InvalidDraft");
+}
+
+#[tokio::test]
+async fn maximum_length_must_produce_a_reply() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api.create_level("Settings", json!({})).await;
+    let uri = format!("/admin/levels/{level_id}");
+    let settings = |maximum_length: u32| {
+        json!({
+            "model_settings": {
+                "temperature": 0.5,
+                "maximum_length": maximum_length,
+                "stop_sequences": [],
+            }
+        })
+    };
+
+    let (status, body) = api
+        .call(Method::PATCH, &uri, Some(MANAGER), settings(0))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "InvalidMaximumLength");
+
+    let (status, _) = api
+        .call(Method::PATCH, &uri, Some(MANAGER), settings(200))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn strict_next_rejects_dangling_levels_and_cycles() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let second = api.create_level("Second", json!({})).await;
+    let first = api
+        .create_level("First", json!({ "is_root": true, "next": [second] }))
+        .await;
+
+    let strict = |level_id: u64| format!("/admin/levels/{level_id}?strict=true");
+    let cases = [
+        (second, json!([999]), "DanglingNext"),
+        (second, json!([second]), "CyclicNext"),
+        (second, json!([first]), "CyclicNext"),
+    ];
+    for (level_id, next, error) in cases {
+        let (status, body) = api
+            .call(
+                Method::PATCH,
+                &strict(level_id),
+                Some(MANAGER),
+                json!({ "next": next }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["type"], error);
+    }
+
+    let third = api.create_level("Third", json!({})).await;
+    let (status, _) = api
+        .call(
+            Method::PATCH,
+            &strict(second),
+            Some(MANAGER),
+            json!({ "next": [third] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn trashed_levels_are_not_published() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api
+        .create_level("Trashed", json!({ "is_root": true }))
+        .await;
+
+    let uri = format!("/admin/levels/{level_id}");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/levels/{level_id}/publish");
+    let (status, body) = api
+        .call(Method::POST, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "LevelDeleted");
+}
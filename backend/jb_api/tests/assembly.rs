@@ -0,0 +1,96 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, MODEL, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+/// Add a component with the given text to the default template
+async fn add_component(api: &TestApi, text: &str) -> u64 {
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/components",
+            Some(MANAGER),
+            json!({ "predecessor": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let component_id = body["component_id"].as_u64().expect("Component id");
+
+    let uri = format!("/admin/prompt/components/{component_id}");
+    let (status, body) = api
+        .call(
+            Method::PUT,
+            &uri,
+            Some(MANAGER),
+            json!({ "new_text": text }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    component_id
+}
+
+#[tokio::test]
+async fn assembly_shows_every_stage() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let mood = add_component(&api, "You are {{ MOOD }}.").await;
+    let greeting = add_component(
+        &api,
+        &format!(r#"Hello {{{{ USER_SUB }}}}. {{% include "{mood}" %}}"#),
+    )
+    .await;
+    let level_id = api
+        .create_level("Level", json!({ "prompt_components": [greeting] }))
+        .await;
+
+    let uri = format!("/admin/levels/{level_id}/prompt?username=alice");
+    let (status, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["model"], MODEL);
+    assert_eq!(
+        body["components"],
+        json!([format!(
+            r#"Hello {{{{ USER_SUB }}}}. {{% include "{mood}" %}}"#
+        )])
+    );
+    assert_eq!(body["rendered"], json!(["Hello alice. You are ."]));
+    assert_eq!(body["instruction"], "Hello alice. You are .");
+    assert_eq!(body["padded"], Value::Null);
+    assert_eq!(
+        body["warnings"],
+        json!(["Undefined variable MOOD renders as an empty string"])
+    );
+
+    let uri = format!("/admin/levels/{level_id}/prompt?published=true");
+    let (status, _) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    api.publish_level(level_id).await;
+    let (status, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["instruction"], "Hello player. You are .");
+}
+
+#[tokio::test]
+async fn assembly_reports_render_errors() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let broken = add_component(&api, r#"{% include "999" %}"#).await;
+    let level_id = api
+        .create_level("Level", json!({ "prompt_components": [broken] }))
+        .await;
+
+    let uri = format!("/admin/levels/{level_id}/prompt");
+    let (status, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "RenderPrompt");
+}
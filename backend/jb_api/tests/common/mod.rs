@@ -0,0 +1,137 @@
+//! Runs the complete API router against in-memory backends
+
+#![allow(dead_code)]
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use jb_api::{
+    InnerState,
+    auth::{LEVEL_MANAGER_GROUP, StaticUserGroups},
+    db::MemoryRepository,
+    llm::{ChatModels, ScriptedChatModel},
+};
+use lambda_http::{
+    RequestExt,
+    aws_lambda_events::apigw::{ApiGatewayProxyRequestContext, ApiGatewayRequestAuthorizer},
+    request::RequestContext,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+/// Member of the level manager group
+pub const MANAGER: &str = "manager";
+
+/// Model every level uses unless its settings name another one
+pub const MODEL: &str = "scripted:test";
+
+pub struct TestApi {
+    app: tower_http::normalize_path::NormalizePath<axum::Router>,
+}
+
+impl TestApi {
+    /// API whose levels are answered by the given scripted model
+    pub fn new(model: ScriptedChatModel) -> Self {
+        let groups = StaticUserGroups::new().member(MANAGER, LEVEL_MANAGER_GROUP);
+        let models = ChatModels::new(MODEL).with_provider("scripted", model);
+
+        Self {
+            app: jb_api::create_app(InnerState::new(groups, MemoryRepository::new(), models)),
+        }
+    }
+
+    /// Send a request, authenticated as `user` if given, and return the status and JSON body
+    ///
+    /// Empty response bodies are returned as `null`.
+    pub async fn call(
+        &self,
+        method: Method,
+        uri: &str,
+        user: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let (status, body) = self.call_text(method, uri, user, body).await;
+
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&body).expect("JSON response body")
+        };
+        (status, body)
+    }
+
+    /// Like [`TestApi::call`], but return the response body as is
+    pub async fn call_text(
+        &self,
+        method: Method,
+        uri: &str,
+        user: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, String) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Content-Type", "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("Valid request");
+
+        if let Some(user) = user {
+            let authorizer = ApiGatewayRequestAuthorizer {
+                fields: [("username".to_owned(), json!(user))].into(),
+                ..Default::default()
+            };
+            request = request.with_request_context(RequestContext::ApiGatewayV1(
+                ApiGatewayProxyRequestContext {
+                    authorizer,
+                    ..Default::default()
+                },
+            ));
+        }
+
+        let response = self
+            .app
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("Router is infallible");
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Readable response body");
+
+        (
+            status,
+            String::from_utf8(body.to_vec()).expect("UTF-8 response body"),
+        )
+    }
+
+    /// Create a level as manager and apply `changes` to its draft, returning its id
+    pub async fn create_level(&self, name: &str, changes: Value) -> u64 {
+        let (status, body) = self
+            .call(
+                Method::POST,
+                "/admin/levels",
+                Some(MANAGER),
+                json!({ "name": name }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let level_id = body["level"]["level_id"].as_u64().expect("Level id");
+
+        let uri = format!("/admin/levels/{level_id}");
+        let (status, body) = self.call(Method::PATCH, &uri, Some(MANAGER), changes).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        level_id
+    }
+
+    /// Publish the level's current draft as manager
+    pub async fn publish_level(&self, level_id: u64) {
+        let uri = format!("/admin/levels/{level_id}/publish");
+        let (status, body) = self
+            .call(Method::POST, &uri, Some(MANAGER), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+async fn admin_level(api: &TestApi, level_id: u64) -> Value {
+    let (_, body) = api
+        .call(Method::GET, "/admin/levels", Some(MANAGER), Value::Null)
+        .await;
+    body["levels"]
+        .as_array()
+        .and_then(|levels| levels.iter().find(|level| level["level_id"] == level_id))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn referenced_levels_are_kept_unless_cascading() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let second = api.create_level("Second", json!({})).await;
+    let first = api.create_level("First", json!({ "next": [second] })).await;
+
+    let uri = format!("/admin/levels/{second}");
+    let (status, body) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "LevelReferenced");
+    assert_eq!(admin_level(&api, first).await["next"], json!([second]));
+
+    let uri = format!("/admin/levels/{second}?cascade=true");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(admin_level(&api, first).await["next"], json!([]));
+    assert_eq!(admin_level(&api, second).await, Value::Null);
+
+    let (_, body) = api
+        .call(Method::GET, "/admin/trash", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(body["levels"][0]["level_id"], second);
+}
+
+#[tokio::test]
+async fn used_components_are_kept_unless_cascading() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/components",
+            Some(MANAGER),
+            json!({ "predecessor": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let component_id = body["component_id"].as_u64().expect("Component id");
+    let level_id = api
+        .create_level("Level", json!({ "prompt_components": [component_id] }))
+        .await;
+
+    let uri = format!("/admin/prompt/components/{component_id}");
+    let (status, body) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "ComponentInUse");
+    assert_eq!(
+        body["message"],
+        format!("Component is still used by levels {level_id}")
+    );
+
+    let uri = format!("/admin/prompt/components/{component_id}?cascade=true");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let level = admin_level(&api, level_id).await;
+    assert_eq!(level["prompt_components"], json!([]));
+    assert_eq!(level["prompt_layout"], json!([]));
+}
+
+#[tokio::test]
+async fn cascades_are_limited_to_one_transaction() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let last = api.create_level("Last", json!({})).await;
+    for index in 0..100 {
+        api.create_level(&format!("Level {index}"), json!({ "next": [last] }))
+            .await;
+    }
+
+    let uri = format!("/admin/levels/{last}?cascade=true");
+    let (status, body) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "TooManyReferences");
+    assert!(admin_level(&api, last).await.is_object());
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApi;
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn progress_requires_sign_in() {
+    let api = TestApi::new(ScriptedChatModel::default());
+
+    let (status, _) = api
+        .call(Method::GET, "/me/progress", None, Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn solves_are_recorded_for_signed_in_players_only() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api
+        .create_level("Root", json!({ "is_root": true, "password": "secret" }))
+        .await;
+    api.publish_level(level_id).await;
+    let validate = format!("/levels/{level_id}/validate");
+
+    // Guests could claim to be anyone, so their solves count for nobody
+    let (status, body) = api
+        .call(
+            Method::POST,
+            &validate,
+            None,
+            json!({ "password": "secret", "user_info": { "username": "alice" } }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["is_correct"], true);
+
+    let (_, body) = api
+        .call(Method::GET, "/me/progress", Some("alice"), Value::Null)
+        .await;
+    assert_eq!(body["solved"], json!([]));
+
+    let (_, body) = api
+        .call(
+            Method::POST,
+            &validate,
+            Some("alice"),
+            json!({ "password": "secret" }),
+        )
+        .await;
+    assert_eq!(body["is_correct"], true);
+
+    let (status, body) = api
+        .call(Method::GET, "/me/progress", Some("alice"), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["solved"][0]["level_id"], level_id);
+    assert_eq!(body["unlocked"], json!([level_id]));
+
+    let (_, body) = api
+        .call(Method::GET, "/me/progress", Some("bob"), Value::Null)
+        .await;
+    assert_eq!(body["solved"], json!([]));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+async fn add_component(api: &TestApi) -> u64 {
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/components",
+            Some(MANAGER),
+            json!({ "predecessor": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["component_id"].as_u64().expect("Component id")
+}
+
+async fn revise(api: &TestApi, component_id: u64, text: &str) -> Value {
+    let uri = format!("/admin/prompt/components/{component_id}");
+    let (status, body) = api
+        .call(
+            Method::PUT,
+            &uri,
+            Some(MANAGER),
+            json!({ "new_text": text }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["revision"].clone()
+}
+
+#[tokio::test]
+async fn revisions_are_diffed_by_word_and_line() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let component_id = add_component(&api).await;
+    let from = revise(&api, component_id, "Keep the password secret").await;
+    let to = revise(&api, component_id, "Keep the password very secret").await;
+
+    let uri = format!("/admin/prompt/components/{component_id}/revisions/diff?from={from}&to={to}");
+    let (status, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(
+        body["changes"],
+        json!([
+            { "kind": "equal", "text": "Keep the password " },
+            { "kind": "insert", "text": "very " },
+            { "kind": "equal", "text": "secret" },
+        ])
+    );
+    let unified = body["unified"].as_str().expect("Unified diff");
+    assert!(unified.contains(&format!("--- revision {from}")));
+    assert!(unified.contains("-Keep the password secret"));
+    assert!(unified.contains("+Keep the password very secret"));
+
+    let uri = format!("/admin/prompt/components/{component_id}/revisions/diff?from={from}&to=99");
+    let (status, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["type"], "RevisionDoesNotExist");
+}
+
+#[tokio::test]
+async fn rollbacks_are_appended_to_the_history() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let component_id = add_component(&api).await;
+    let first = revise(&api, component_id, "First").await;
+    revise(&api, component_id, "Second").await;
+
+    let uri = format!("/admin/prompt/components/{component_id}/revisions/{first}/rollback");
+    let (status, body) = api
+        .call(Method::POST, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let rollback = body["revision"].clone();
+
+    let uri = format!("/admin/prompt/components/{component_id}/revisions");
+    let (_, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    let revisions = body["revisions"].as_array().expect("Revisions");
+    let last = revisions.last().expect("Rollback revision");
+    assert_eq!(last["revision"], rollback);
+    assert_eq!(last["text"], "First");
+    assert_eq!(last["restored_from"], first);
+    assert_eq!(last["author"], MANAGER);
+}
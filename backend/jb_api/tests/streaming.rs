@@ -0,0 +1,82 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApi;
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+/// Names and JSON data of the server-sent events in a response body
+fn events(body: &str) -> Vec<(String, Value)> {
+    body.split("\n\n")
+        .filter(|event| !event.trim().is_empty())
+        .map(|event| {
+            let field = |name: &str| {
+                event
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(str::trim)
+                    .expect("Event field")
+            };
+            let data = serde_json::from_str(field("data:")).expect("JSON event data");
+            (field("event:").to_owned(), data)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn replies_are_streamed_and_recorded() {
+    let api = TestApi::new(ScriptedChatModel::new("Hello there"));
+    let level_id = api.create_level("Level", json!({ "is_root": true })).await;
+    api.publish_level(level_id).await;
+
+    let uri = format!("/levels/{level_id}/chat/session/stream");
+    let message = json!({ "message": "Hi", "user_info": { "username": "alice" } });
+    let (status, body) = api
+        .call_text(Method::POST, &uri, Some("alice"), message)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        events(&body),
+        [
+            ("chunk".to_owned(), json!({ "text": "Hello there" })),
+            (
+                "done".to_owned(),
+                json!({ "reply": "Hello there", "level_solved": false })
+            ),
+        ]
+    );
+
+    let uri = format!("/levels/{level_id}/chat/session");
+    let (_, body) = api
+        .call(Method::GET, &uri, Some("alice"), Value::Null)
+        .await;
+    assert_eq!(body["turns"][0]["reply"], "Hello there");
+}
+
+#[tokio::test]
+async fn guarded_replies_are_only_sent_complete() {
+    let api = TestApi::new(ScriptedChatModel::new("The password is swordfish"));
+    let level_id = api
+        .create_level(
+            "Level",
+            json!({
+                "is_root": true,
+                "password": "swordfish",
+                "output_guards": [{ "type": "redact_password", "replacement": "*****" }],
+            }),
+        )
+        .await;
+    api.publish_level(level_id).await;
+
+    let uri = format!("/levels/{level_id}/chat/session/stream");
+    let message = json!({ "message": "Hi", "user_info": { "username": "alice" } });
+    let (status, body) = api.call_text(Method::POST, &uri, None, message).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        events(&body),
+        [(
+            "done".to_owned(),
+            json!({ "reply": "The password is *****", "level_solved": false })
+        )]
+    );
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+async fn create_template(api: &TestApi) -> String {
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/templates",
+            Some(MANAGER),
+            json!({ "name": "Spare" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["template"]["template_id"]
+        .as_str()
+        .expect("Template id")
+        .to_owned()
+}
+
+async fn add_component(api: &TestApi, template_id: &str) -> u64 {
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/components",
+            Some(MANAGER),
+            json!({ "template_id": template_id, "predecessor": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["component_id"].as_u64().expect("Component id")
+}
+
+#[tokio::test]
+async fn deleting_a_template_trashes_its_components() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let template_id = create_template(&api).await;
+    let component_id = add_component(&api, &template_id).await;
+
+    let uri = format!("/admin/prompt/templates/{template_id}");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = api
+        .call(Method::GET, "/admin/trash", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(body["components"][0]["id"], component_id);
+
+    let restore = format!("/admin/trash/components/{component_id}/restore");
+    let (status, body) = api
+        .call(Method::POST, &restore, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "TemplateDeleted");
+
+    let purge = format!("/admin/trash/components/{component_id}");
+    let (status, _) = api
+        .call(Method::DELETE, &purge, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn deleting_a_missing_template_fails() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let template_id = create_template(&api).await;
+
+    let uri = format!("/admin/prompt/templates/{template_id}");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["type"], "DoesNotExist");
+}
+
+#[tokio::test]
+async fn reordering_is_limited_to_one_transaction() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let template_id = create_template(&api).await;
+    let mut components = Vec::new();
+    for _ in 0..101 {
+        components.push(add_component(&api, &template_id).await);
+    }
+
+    let uri = format!("/admin/prompt/templates/{template_id}/order");
+    let order = json!({ "components": components });
+    let (status, body) = api.call(Method::PUT, &uri, Some(MANAGER), order).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "TooManyComponents");
+
+    let uri = format!("/admin/prompt/templates/{template_id}/rebalance");
+    let (status, body) = api
+        .call(Method::POST, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["type"], "TooManyComponents");
+}
+
+#[tokio::test]
+async fn trashed_components_keep_their_place_when_reordering() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let template_id = create_template(&api).await;
+    let mut components = Vec::new();
+    for _ in 0..3 {
+        components.push(add_component(&api, &template_id).await);
+    }
+    // Components are added at the start, so the third one comes first
+    let [third, second, first] = components[..] else {
+        unreachable!()
+    };
+
+    let uri = format!("/admin/prompt/components/{second}");
+    let (status, _) = api
+        .call(Method::DELETE, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/prompt/templates/{template_id}/order");
+    let order = json!({ "components": [third, first] });
+    let (status, body) = api.call(Method::PUT, &uri, Some(MANAGER), order).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let uri = format!("/admin/trash/components/{second}/restore");
+    let (status, _) = api
+        .call(Method::POST, &uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/admin/prompt/components?template_id={template_id}");
+    let (_, body) = api
+        .call(Method::GET, &uri, Some(MANAGER), Value::Null)
+        .await;
+    let order: Vec<_> = body["components"]
+        .as_array()
+        .expect("Components")
+        .iter()
+        .map(|component| component["id"].clone())
+        .collect();
+    assert_eq!(order, [json!(third), json!(first), json!(second)]);
+}
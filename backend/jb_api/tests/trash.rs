@@ -0,0 +1,130 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::{Value, json};
+
+async fn trash(api: &TestApi) -> Value {
+    let (status, body) = api
+        .call(Method::GET, "/admin/trash", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body
+}
+
+async fn add_component(api: &TestApi) -> u64 {
+    let (status, body) = api
+        .call(
+            Method::POST,
+            "/admin/prompt/components",
+            Some(MANAGER),
+            json!({ "predecessor": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["component_id"].as_u64().expect("Component id")
+}
+
+async fn delete(api: &TestApi, uri: &str) {
+    let (status, body) = api
+        .call(Method::DELETE, uri, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+#[tokio::test]
+async fn deleted_levels_are_restored_from_the_trash() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api.create_level("Level", json!({})).await;
+    let component_id = add_component(&api).await;
+
+    let restore = format!("/admin/trash/levels/{level_id}/restore");
+    let (status, body) = api
+        .call(Method::POST, &restore, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["type"], "NotInTrash");
+
+    delete(&api, &format!("/admin/levels/{level_id}")).await;
+    delete(&api, &format!("/admin/prompt/components/{component_id}")).await;
+    let body = trash(&api).await;
+    assert_eq!(body["levels"][0]["level_id"], level_id);
+    assert_eq!(body["levels"][0]["name"], "Level");
+    assert_eq!(body["components"][0]["id"], component_id);
+
+    let (status, _) = api
+        .call(Method::POST, &restore, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(trash(&api).await["levels"], json!([]));
+
+    let (_, body) = api
+        .call(Method::GET, "/admin/levels", Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(body["levels"][0]["level_id"], level_id);
+
+    let restore = format!("/admin/trash/components/{component_id}/restore");
+    let (status, _) = api
+        .call(Method::POST, &restore, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(trash(&api).await["components"], json!([]));
+}
+
+#[tokio::test]
+async fn purged_levels_are_gone() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let level_id = api.create_level("Level", json!({ "is_root": true })).await;
+    api.publish_level(level_id).await;
+
+    let purge = format!("/admin/trash/levels/{level_id}");
+    let (status, body) = api
+        .call(Method::DELETE, &purge, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["type"], "NotInTrash");
+
+    delete(&api, &format!("/admin/levels/{level_id}")).await;
+    delete(&api, &purge).await;
+    assert_eq!(trash(&api).await["levels"], json!([]));
+
+    let (status, _) = api
+        .call(Method::DELETE, &purge, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn purged_components_lose_their_revisions() {
+    let api = TestApi::new(ScriptedChatModel::default());
+    let component_id = add_component(&api).await;
+
+    let uri = format!("/admin/prompt/components/{component_id}");
+    for text in ["First", "Second"] {
+        let (status, body) = api
+            .call(
+                Method::PUT,
+                &uri,
+                Some(MANAGER),
+                json!({ "new_text": text }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+
+    let revisions = format!("/admin/prompt/components/{component_id}/revisions");
+    let (_, body) = api
+        .call(Method::GET, &revisions, Some(MANAGER), Value::Null)
+        .await;
+    assert!(!body["revisions"].as_array().expect("Revisions").is_empty());
+
+    delete(&api, &uri).await;
+    delete(&api, &format!("/admin/trash/components/{component_id}")).await;
+    assert_eq!(trash(&api).await["components"], json!([]));
+
+    let (_, body) = api
+        .call(Method::GET, &revisions, Some(MANAGER), Value::Null)
+        .await;
+    assert_eq!(body["revisions"], json!([]));
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{MANAGER, TestApi};
+use jb_api::llm::ScriptedChatModel;
+use serde_json::json;
+
+/// Published root level leading to a published locked level
+async fn two_levels(api: &TestApi) -> (u64, u64) {
+    let locked = api.create_level("Locked", json!({})).await;
+    let root = api
+        .create_level(
+            "Root",
+            json!({ "is_root": true, "password": "secret", "next": [locked] }),
+        )
+        .await;
+    api.publish_level(locked).await;
+    api.publish_level(root).await;
+    (root, locked)
+}
+
+async fn chat(api: &TestApi, level_id: u64, user: Option<&str>, claimed: &str) -> StatusCode {
+    let uri = format!("/levels/{level_id}/chat/session");
+    let body = json!({ "message": "Hello", "user_info": { "username": claimed } });
+    api.call(Method::POST, &uri, user, body).await.0
+}
+
+#[tokio::test]
+async fn locked_levels_open_after_solving_a_predecessor() {
+    let api = TestApi::new(ScriptedChatModel::new("Hi"));
+    let (root, locked) = two_levels(&api).await;
+
+    assert_eq!(chat(&api, root, None, "guest").await, StatusCode::OK);
+    assert_eq!(
+        chat(&api, locked, None, "guest").await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        chat(&api, locked, Some("alice"), "alice").await,
+        StatusCode::FORBIDDEN
+    );
+
+    let uri = format!("/levels/{root}/validate");
+    let (status, body) = api
+        .call(
+            Method::POST,
+            &uri,
+            Some("alice"),
+            json!({ "password": "secret" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["is_correct"], true);
+
+    assert_eq!(
+        chat(&api, locked, Some("alice"), "bob").await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn claimed_usernames_do_not_unlock_levels() {
+    let api = TestApi::new(ScriptedChatModel::new("Hi"));
+    let (root, locked) = two_levels(&api).await;
+
+    let uri = format!("/levels/{root}/validate");
+    api.call(
+        Method::POST,
+        &uri,
+        Some("alice"),
+        json!({ "password": "secret" }),
+    )
+    .await;
+
+    assert_eq!(
+        chat(&api, locked, None, "alice").await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        chat(&api, locked, Some("bob"), "alice").await,
+        StatusCode::FORBIDDEN
+    );
+
+    let uri = format!("/levels/{locked}/validate");
+    let (status, body) = api
+        .call(Method::POST, &uri, None, json!({ "password": "anything" }))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["type"], "LevelLocked");
+}
+
+#[tokio::test]
+async fn level_managers_play_locked_levels() {
+    let api = TestApi::new(ScriptedChatModel::new("Hi"));
+    let (_, locked) = two_levels(&api).await;
+
+    assert_eq!(
+        chat(&api, locked, Some(MANAGER), MANAGER).await,
+        StatusCode::OK
+    );
+}
@@ -1,16 +1,16 @@
 use std::vec;
 
 use alcoholic_jwt::{JWKS, ValidJWT, Validation};
-use anyhow::{anyhow, Context};
+use anyhow::{Context, anyhow};
 use aws_lambda_events::{
     apigw::{
         ApiGatewayCustomAuthorizerPolicy, ApiGatewayCustomAuthorizerRequest,
-        ApiGatewayCustomAuthorizerResponse,
+        ApiGatewayCustomAuthorizerRequestTypeRequest, ApiGatewayCustomAuthorizerResponse,
     },
     iam::{IamPolicyEffect, IamPolicyStatement},
 };
 use lambda_runtime::LambdaEvent;
-use serde_json::json;
+use serde_json::{Value, json};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -24,40 +24,96 @@ async fn main() -> anyhow::Result<()> {
         .json()
         .expect("Unable to deserialize jwks.json");
 
-    lambda_runtime::run(lambda_runtime::service_fn(async |event| {
-        Ok::<_, lambda_runtime::Error>(
-            authorizer(event, &issuer, jwks)
-                .await
-                .unwrap_or_else(unauthorized_response),
-        )
-    }))
+    lambda_runtime::run(lambda_runtime::service_fn(
+        async |event: LambdaEvent<Value>| {
+            Ok::<_, lambda_runtime::Error>(
+                handle(event.payload, &issuer, jwks)
+                    .await
+                    .unwrap_or_else(unauthorized_response),
+            )
+        },
+    ))
     .await
     .unwrap();
 
     Ok(())
 }
 
+/// Token authorizers guard routes requiring a sign in,
+/// request authorizers guard routes where signing in is optional
+async fn handle(
+    payload: Value,
+    issuer: &str,
+    jwks: &JWKS,
+) -> Result<ApiGatewayCustomAuthorizerResponse, anyhow::Error> {
+    if payload.get("type").and_then(Value::as_str) == Some("REQUEST") {
+        optional_authorizer(payload, issuer, jwks).await
+    } else {
+        authorizer(payload, issuer, jwks).await
+    }
+}
+
 async fn authorizer(
-    event: LambdaEvent<ApiGatewayCustomAuthorizerRequest>,
-    issuer: &String,
+    payload: Value,
+    issuer: &str,
     jwks: &JWKS,
 ) -> Result<ApiGatewayCustomAuthorizerResponse, anyhow::Error> {
-    let token = event
-        .payload
+    let request: ApiGatewayCustomAuthorizerRequest =
+        serde_json::from_value(payload).context("Invalid token authorizer event")?;
+    let token = request
         .authorization_token
         .expect("Missing authorization token");
-    let method_arn = event.payload.method_arn.expect("Missing method ARN");
+    let method_arn = request.method_arn.expect("Missing method ARN");
+
+    let (sub, username) = verify_token(&token, issuer, jwks)?;
 
-    let kid = alcoholic_jwt::token_kid(&token)
+    Ok(create_response(
+        Some(sub.clone()),
+        Some(method_arn),
+        json!({ "sub": sub, "username": username }),
+    ))
+}
+
+/// Like [`authorizer`], but lets requests without a token through without an identity
+async fn optional_authorizer(
+    payload: Value,
+    issuer: &str,
+    jwks: &JWKS,
+) -> Result<ApiGatewayCustomAuthorizerResponse, anyhow::Error> {
+    let request: ApiGatewayCustomAuthorizerRequestTypeRequest =
+        serde_json::from_value(payload).context("Invalid request authorizer event")?;
+    let method_arn = request.method_arn.context("Missing method ARN")?;
+
+    let Some(token) = request.headers.get("Authorization") else {
+        return Ok(create_response(
+            Some("anonymous".to_owned()),
+            Some(method_arn),
+            json!({}),
+        ));
+    };
+    let token = token.to_str().context("Invalid authorization header")?;
+
+    let (sub, username) = verify_token(token, issuer, jwks)?;
+
+    Ok(create_response(
+        Some(sub.clone()),
+        Some(method_arn),
+        json!({ "sub": sub, "username": username }),
+    ))
+}
+
+/// Validate the token and return its subject and username
+fn verify_token(token: &str, issuer: &str, jwks: &JWKS) -> Result<(String, String), anyhow::Error> {
+    let kid = alcoholic_jwt::token_kid(token)
         .context("Invalid token header")?
         .context("Missing token kid field")?;
 
     let jwk = jwks.find(&kid).context("Unknown token kid")?;
     let ValidJWT { claims, .. } = alcoholic_jwt::validate(
-        &token,
+        token,
         jwk,
         vec![
-            Validation::Issuer(issuer.clone()),
+            Validation::Issuer(issuer.to_owned()),
             Validation::NotExpired,
         ],
     )?;
@@ -75,11 +131,7 @@ async fn authorizer(
         .as_str()
         .unwrap();
 
-    Ok(create_response(
-        Some(sub.to_owned()),
-        Some(method_arn),
-        json!({ "sub": sub, "username": username }),
-    ))
+    Ok((sub.to_owned(), username.to_owned()))
 }
 
 fn unauthorized_response(cause: anyhow::Error) -> ApiGatewayCustomAuthorizerResponse {
@@ -5,6 +5,12 @@ data "external" "api_spec" {
 
 locals {
   api_spec = jsondecode(data.external.api_spec.result.encoded)
+  auth_security = {
+    none     = {}
+    required = { security = [{ cognito-authorizer = [] }] }
+    # Anonymous requests pass without an identity, signed in users are verified
+    optional = { security = [{ cognito-optional-authorizer = [] }] }
+  }
   extra_paths = {
     "/{notfound+}" = {
       x-amazon-apigateway-any-method = {
@@ -51,6 +57,18 @@ resource "aws_api_gateway_rest_api" "rest_api" {
             authorizerResultTtlInSeconds = 0 # Caching requires us to return ALL allowed method ARNs, not just the current one
           }
         }
+        cognito-optional-authorizer = {
+          type                         = "apiKey",
+          name                         = "Authorization",
+          in                           = "header",
+          x-amazon-apigateway-authtype = "custom",
+          x-amazon-apigateway-authorizer = {
+            # Request authorizers without identity source are invoked even without a token
+            type          = "request",
+            authorizerUri = aws_lambda_function.authorizer.invoke_arn
+            authorizerResultTtlInSeconds = 0
+          }
+        }
       }
     }
     x-amazon-apigateway-gateway-responses = {
@@ -65,7 +83,7 @@ resource "aws_api_gateway_rest_api" "rest_api" {
     paths = merge({
       for route, methods in local.api_spec : route => {
         for method, properties in methods : method => merge(
-          properties.require_auth ? { security = [{ cognito-authorizer = [] }] } : {},
+          local.auth_security[properties.auth],
           {
             x-amazon-apigateway-integration = {
               type                 = "AWS_PROXY",
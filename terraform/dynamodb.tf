@@ -47,3 +47,110 @@ resource "aws_dynamodb_table" "levels" {
     type = "N"
   }
 }
+
+resource "aws_dynamodb_table" "chat_sessions" {
+  name = "jb_chat_sessions"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "session_id"
+
+  attribute {
+    name = "session_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "username"
+    type = "S"
+  }
+
+  attribute {
+    name = "updated_at"
+    type = "N"
+  }
+
+  global_secondary_index {
+    name = "username-index"
+    hash_key = "username"
+    range_key = "updated_at"
+    projection_type = "ALL"
+  }
+}
+
+resource "aws_dynamodb_table" "chat_turns" {
+  name = "jb_chat_turns"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "session_id"
+  range_key = "turn"
+
+  attribute {
+    name = "session_id"
+    type = "S"
+  }
+
+  attribute {
+    name = "turn"
+    type = "N"
+  }
+}
+
+resource "aws_dynamodb_table" "level_solves" {
+  name = "jb_level_solves"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "username"
+  range_key = "level_id"
+
+  attribute {
+    name = "username"
+    type = "S"
+  }
+
+  attribute {
+    name = "level_id"
+    type = "N"
+  }
+}
+
+resource "aws_dynamodb_table" "templates" {
+  name = "jb_templates"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "template_id"
+
+  attribute {
+    name = "template_id"
+    type = "S"
+  }
+}
+
+resource "aws_dynamodb_table" "component_revisions" {
+  name = "jb_component_revisions"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "component_id"
+  range_key = "revision"
+
+  attribute {
+    name = "component_id"
+    type = "N"
+  }
+
+  attribute {
+    name = "revision"
+    type = "N"
+  }
+}
+
+resource "aws_dynamodb_table" "published_levels" {
+  name = "jb_published_levels"
+  billing_mode = "PAY_PER_REQUEST"
+  hash_key = "level_id"
+  range_key = "version"
+
+  attribute {
+    name = "level_id"
+    type = "N"
+  }
+
+  attribute {
+    name = "version"
+    type = "N"
+  }
+}